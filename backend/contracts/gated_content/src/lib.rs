@@ -0,0 +1,130 @@
+#![no_std]
+
+//! Example contract gating its content on a SoroSub subscription.
+//!
+//! Articles are published by the merchant and can only be read by addresses
+//! that `SoroSub::has_access` reports as paid-up subscribers of the merchant.
+
+use soroban_sdk::{
+    contract, contractclient, contractimpl, contracttype, Address, Env, String, Symbol,
+};
+
+// ============================================================================
+// STORAGE TYPES
+// ============================================================================
+
+/// Storage keys for the contract
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    SoroSub,         // SoroSub contract checked for access
+    Merchant,        // Merchant whose subscribers can read
+    Article(Symbol), // Published article body
+}
+
+// ============================================================================
+// SOROSUB INTERFACE
+// ============================================================================
+
+/// The subset of the SoroSub contract (`contracts/sorosub`) used to check
+/// access. Any contract can gate its features the same way.
+#[contractclient(name = "SoroSubClient")]
+pub trait SoroSubInterface {
+    /// Whether `subscriber` is a paid-up subscriber of `merchant`
+    fn has_access(env: Env, subscriber: Address, merchant: Address) -> bool;
+}
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// TTL: 30 days in ledgers (assuming ~5 second ledgers)
+const THIRTY_DAYS_LEDGERS: u32 = 30 * 24 * 60 * 60 / 5;
+
+// ============================================================================
+// CONTRACT
+// ============================================================================
+
+#[contract]
+pub struct GatedContentContract;
+
+#[contractimpl]
+impl GatedContentContract {
+    /// Initialize the gate with the SoroSub contract and merchant
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `sorosub` - The SoroSub contract to check access with
+    /// * `merchant` - The merchant whose subscribers can read articles
+    pub fn initialize(env: Env, sorosub: Address, merchant: Address) {
+        if env.storage().instance().has(&DataKey::SoroSub) {
+            panic!("Contract already initialized");
+        }
+
+        env.storage().instance().set(&DataKey::SoroSub, &sorosub);
+        env.storage().instance().set(&DataKey::Merchant, &merchant);
+
+        // Extend instance TTL
+        env.storage()
+            .instance()
+            .extend_ttl(THIRTY_DAYS_LEDGERS, THIRTY_DAYS_LEDGERS);
+    }
+
+    /// Publish an article (merchant only)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `id` - The article's identifier
+    /// * `body` - The article's content
+    pub fn publish(env: Env, id: Symbol, body: String) {
+        let merchant: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Merchant)
+            .expect("Contract not initialized");
+        merchant.require_auth();
+
+        let key = DataKey::Article(id);
+        env.storage().persistent().set(&key, &body);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, THIRTY_DAYS_LEDGERS, THIRTY_DAYS_LEDGERS);
+    }
+
+    /// Read an article (subscribers only)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `reader` - The reader's address (must sign)
+    /// * `id` - The article's identifier
+    ///
+    /// # Returns
+    /// * The article's content
+    pub fn read(env: Env, reader: Address, id: Symbol) -> String {
+        reader.require_auth();
+
+        if !Self::can_read(env.clone(), reader) {
+            panic!("Subscription required");
+        }
+
+        env.storage()
+            .persistent()
+            .get(&DataKey::Article(id))
+            .expect("Article not found")
+    }
+
+    /// Check whether an address can read articles
+    pub fn can_read(env: Env, reader: Address) -> bool {
+        let sorosub: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::SoroSub)
+            .expect("Contract not initialized");
+        let merchant: Address = env.storage().instance().get(&DataKey::Merchant).unwrap();
+
+        SoroSubClient::new(&env, &sorosub).has_access(&reader, &merchant)
+    }
+}
+
+#[cfg(test)]
+mod test;
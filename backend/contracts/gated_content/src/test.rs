@@ -0,0 +1,146 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{
+    symbol_short,
+    testutils::{Address as _, Ledger, LedgerInfo},
+    token::{StellarAssetClient, TokenClient},
+    Address, Env, String,
+};
+use sorosub::{SoroSubContract, SoroSubContractClient};
+
+/// Helper to set initial ledger state with proper protocol version
+fn setup_ledger(env: &Env) {
+    env.ledger().set(LedgerInfo {
+        timestamp: 1000,
+        protocol_version: 25,
+        sequence_number: 100,
+        network_id: Default::default(),
+        base_reserve: 10,
+        min_temp_entry_ttl: 100,
+        min_persistent_entry_ttl: 100,
+        max_entry_ttl: 10000,
+    });
+}
+
+/// Deployed SoroSub and gate, with a token the reader has approved
+struct Setup<'a> {
+    sorosub: SoroSubContractClient<'a>,
+    gate: GatedContentContractClient<'a>,
+    token: TokenClient<'a>,
+    merchant: Address,
+    reader: Address,
+}
+
+/// Helper to deploy SoroSub and a gate for one merchant, with one article
+fn setup(env: &Env) -> Setup<'_> {
+    setup_ledger(env);
+
+    let admin = Address::generate(env);
+    let merchant = Address::generate(env);
+    let reader = Address::generate(env);
+
+    let sorosub_id = env.register(SoroSubContract, ());
+    let sorosub = SoroSubContractClient::new(env, &sorosub_id);
+    sorosub.initialize(&admin, &Address::generate(env));
+
+    let gate_id = env.register(GatedContentContract, ());
+    let gate = GatedContentContractClient::new(env, &gate_id);
+    gate.initialize(&sorosub_id, &merchant);
+    gate.publish(&symbol_short!("intro"), &String::from_str(env, "Welcome"));
+
+    let token_address = env.register_stellar_asset_contract_v2(admin).address();
+    let token = TokenClient::new(env, &token_address);
+    StellarAssetClient::new(env, &token_address).mint(&reader, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token.approve(&reader, &sorosub_id, &100, &expiration_ledger);
+
+    Setup {
+        sorosub,
+        gate,
+        token,
+        merchant,
+        reader,
+    }
+}
+
+#[test]
+fn test_subscriber_can_read() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let s = setup(&env);
+
+    assert!(!s.gate.can_read(&s.reader));
+
+    s.sorosub
+        .create_subscription(&s.reader, &s.merchant, &s.token.address, &100, &3600);
+    s.sorosub.collect_payment(&s.reader, &s.merchant);
+
+    assert!(s.gate.can_read(&s.reader));
+    assert_eq!(
+        s.gate.read(&s.reader, &symbol_short!("intro")),
+        String::from_str(&env, "Welcome")
+    );
+}
+
+#[test]
+#[should_panic(expected = "Subscription required")]
+fn test_non_subscriber_cannot_read() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let s = setup(&env);
+
+    s.gate.read(&s.reader, &symbol_short!("intro"));
+}
+
+#[test]
+fn test_unpaid_subscriber_cannot_read() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let s = setup(&env);
+
+    // Subscribed, but the first payment was never collected
+    s.sorosub
+        .create_subscription(&s.reader, &s.merchant, &s.token.address, &100, &3600);
+    assert!(!s.gate.can_read(&s.reader));
+    assert!(s.gate.try_read(&s.reader, &symbol_short!("intro")).is_err());
+}
+
+#[test]
+fn test_access_lapses_after_grace_period() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let s = setup(&env);
+
+    s.sorosub
+        .create_subscription(&s.reader, &s.merchant, &s.token.address, &100, &3600);
+    s.sorosub.collect_payment(&s.reader, &s.merchant);
+
+    // The reader's balance is spent, so the next payment can't be collected
+    env.ledger().with_mut(|l| l.timestamp += 3600);
+    s.sorosub.collect_payment(&s.reader, &s.merchant);
+    assert!(s.gate.can_read(&s.reader));
+
+    // 7 days of grace later, the gate closes
+    env.ledger().with_mut(|l| l.timestamp += 7 * 24 * 60 * 60);
+    assert!(!s.gate.can_read(&s.reader));
+}
+
+#[test]
+fn test_team_member_can_read() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let s = setup(&env);
+    let member = Address::generate(&env);
+
+    s.sorosub
+        .create_team_subscription(&s.reader, &s.merchant, &s.token.address, &50, &2, &3600);
+    s.sorosub.collect_payment(&s.reader, &s.merchant);
+    assert!(!s.gate.can_read(&member));
+
+    s.sorosub.add_member(&s.reader, &s.merchant, &member);
+    assert_eq!(
+        s.gate.read(&member, &symbol_short!("intro")),
+        String::from_str(&env, "Welcome")
+    );
+}
@@ -0,0 +1,523 @@
+#![no_std]
+
+use soroban_sdk::{contract, contractevent, contractimpl, contracttype, log, token, Address, Env};
+
+// ============================================================================
+// STORAGE TYPES
+// ============================================================================
+
+/// Per-token vault accounting
+#[derive(Clone)]
+#[contracttype]
+pub struct PoolReserves {
+    pub cash: i128,         // Tokens held by the vault and available to lend or withdraw
+    pub borrowed: i128,     // Principal currently advanced to the borrower
+    pub reserve: i128,      // Bad-debt reserve (not owned by LPs, covers write-offs first)
+    pub total_shares: i128, // LP shares outstanding
+}
+
+/// Storage keys for the contract
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    Reserves(Address),        // Vault accounting per token
+    Shares(Address, Address), // LP share balance (lp, token)
+    Borrower,                 // Contract allowed to draw advances (SoroSub)
+    Admin,                    // Admin address for initialization
+    Initialized,              // Whether contract is initialized
+}
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+/// Version carried by every event payload, bumped when a payload changes
+pub const EVENT_VERSION: u32 = 1;
+
+/// An LP deposited tokens for shares
+#[contractevent(topics = ["deposit"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Deposit {
+    #[topic]
+    pub lp: Address,
+    #[topic]
+    pub token: Address,
+    pub version: u32,
+    pub amount: i128, // Tokens deposited
+    pub shares: i128, // Shares minted
+}
+
+/// An LP burned shares for their pro-rata amount
+#[contractevent(topics = ["withdraw"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Withdraw {
+    #[topic]
+    pub lp: Address,
+    #[topic]
+    pub token: Address,
+    pub version: u32,
+    pub amount: i128, // Tokens paid out
+    pub shares: i128, // Shares burned
+}
+
+/// The borrower drew an advance
+#[contractevent(topics = ["draw"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Draw {
+    #[topic]
+    pub token: Address,
+    #[topic]
+    pub to: Address,
+    pub version: u32,
+    pub amount: i128,   // Tokens advanced
+    pub borrowed: i128, // Principal outstanding afterwards
+}
+
+/// The borrower returned principal and fee income
+#[contractevent(topics = ["repay"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Repay {
+    #[topic]
+    pub token: Address,
+    pub version: u32,
+    pub principal: i128, // Principal returned
+    pub interest: i128,  // Fee income for LPs
+    pub reserve: i128,   // Fee income for the bad-debt reserve
+    pub borrowed: i128,  // Principal outstanding afterwards
+}
+
+/// Defaulted principal was written off
+#[contractevent(topics = ["write_off"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WriteOff {
+    #[topic]
+    pub token: Address,
+    pub version: u32,
+    pub amount: i128,    // Principal written off
+    pub covered: i128,   // Portion covered by the bad-debt reserve
+    pub uncovered: i128, // Portion absorbed by LP shares
+}
+
+/// The admin changed the contract allowed to draw advances
+#[contractevent(topics = ["admin", "set_borrower"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BorrowerUpdated {
+    pub version: u32,
+    pub admin: Address,    // Admin that made the change
+    pub borrower: Address, // New borrower
+}
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// TTL: 30 days in ledgers (assuming ~5 second ledgers)
+const THIRTY_DAYS_LEDGERS: u32 = 30 * 24 * 60 * 60 / 5;
+
+/// Entries touched with less than 15 days left are topped back up to 30 days
+const TTL_THRESHOLD_LEDGERS: u32 = THIRTY_DAYS_LEDGERS / 2;
+
+// ============================================================================
+// CONTRACT
+// ============================================================================
+
+#[contract]
+pub struct LiquidityPoolContract;
+
+#[contractimpl]
+impl LiquidityPoolContract {
+    /// Initialize the vault with admin and borrower
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `admin` - The admin address
+    /// * `borrower` - The contract allowed to draw BNPL advances (SoroSub)
+    pub fn initialize(env: Env, admin: Address, borrower: Address) {
+        // Check if already initialized
+        if env.storage().instance().has(&DataKey::Initialized) {
+            panic!("Contract already initialized");
+        }
+
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage().instance().set(&DataKey::Borrower, &borrower);
+        env.storage().instance().set(&DataKey::Initialized, &true);
+
+        // Extend instance TTL
+        env.storage()
+            .instance()
+            .extend_ttl(THIRTY_DAYS_LEDGERS, THIRTY_DAYS_LEDGERS);
+    }
+
+    /// Deposit tokens into the vault in exchange for LP shares
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `lp` - The liquidity provider (must sign)
+    /// * `token` - The token to deposit
+    /// * `amount` - Amount of tokens to deposit
+    ///
+    /// # Returns
+    /// * The number of shares minted
+    pub fn deposit(env: Env, lp: Address, token: Address, amount: i128) -> i128 {
+        lp.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if amount <= 0 {
+            panic!("Deposit amount must be positive");
+        }
+
+        let mut reserves = Self::load_reserves(&env, &token);
+
+        // Fee income repaid after every LP left belongs to no one; it joins
+        // the bad-debt reserve rather than going to the next depositor
+        if reserves.total_shares == 0 && reserves.cash > 0 {
+            reserves.reserve += reserves.cash;
+            reserves.cash = 0;
+        }
+
+        // Shares are priced against everything the vault is owed, not just cash
+        let total_assets = reserves.cash + reserves.borrowed;
+        let shares = if reserves.total_shares == 0 {
+            if reserves.borrowed > 0 {
+                panic!("Vault has assets but no shares");
+            }
+            amount
+        } else if total_assets == 0 {
+            // Existing shares are worthless; new deposits would be split with them
+            panic!("Vault has no assets backing its shares");
+        } else {
+            amount * reserves.total_shares / total_assets
+        };
+        if shares <= 0 {
+            panic!("Deposit too small to mint shares");
+        }
+
+        let contract_address = env.current_contract_address();
+        token::Client::new(&env, &token).transfer(&lp, &contract_address, &amount);
+
+        reserves.cash += amount;
+        reserves.total_shares += shares;
+        Self::save_reserves(&env, &token, &reserves);
+
+        let shares_key = DataKey::Shares(lp.clone(), token.clone());
+        let balance: i128 = env.storage().persistent().get(&shares_key).unwrap_or(0);
+        env.storage()
+            .persistent()
+            .set(&shares_key, &(balance + shares));
+        env.storage().persistent().extend_ttl(
+            &shares_key,
+            THIRTY_DAYS_LEDGERS,
+            THIRTY_DAYS_LEDGERS,
+        );
+
+        log!(
+            &env,
+            "Deposit: {} tokens by {} for {} shares",
+            amount,
+            lp,
+            shares
+        );
+
+        Deposit {
+            lp,
+            token,
+            version: EVENT_VERSION,
+            amount,
+            shares,
+        }
+        .publish(&env);
+
+        shares
+    }
+
+    /// Burn LP shares and withdraw the pro-rata amount of tokens
+    ///
+    /// Only idle cash can be withdrawn; funds currently advanced to the
+    /// borrower become withdrawable again as they are repaid.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `lp` - The liquidity provider (must sign)
+    /// * `token` - The token to withdraw
+    /// * `shares` - Number of shares to burn
+    ///
+    /// # Returns
+    /// * The amount of tokens withdrawn
+    pub fn withdraw(env: Env, lp: Address, token: Address, shares: i128) -> i128 {
+        lp.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if shares <= 0 {
+            panic!("Share amount must be positive");
+        }
+
+        let shares_key = DataKey::Shares(lp.clone(), token.clone());
+        let balance: i128 = env.storage().persistent().get(&shares_key).unwrap_or(0);
+        if shares > balance {
+            panic!("Insufficient shares");
+        }
+
+        let mut reserves = Self::load_reserves(&env, &token);
+        let amount = shares * (reserves.cash + reserves.borrowed) / reserves.total_shares;
+        if amount > reserves.cash {
+            panic!("Insufficient pool liquidity");
+        }
+
+        reserves.cash -= amount;
+        reserves.total_shares -= shares;
+        Self::save_reserves(&env, &token, &reserves);
+
+        if balance == shares {
+            env.storage().persistent().remove(&shares_key);
+        } else {
+            env.storage()
+                .persistent()
+                .set(&shares_key, &(balance - shares));
+            env.storage().persistent().extend_ttl(
+                &shares_key,
+                THIRTY_DAYS_LEDGERS,
+                THIRTY_DAYS_LEDGERS,
+            );
+        }
+
+        let contract_address = env.current_contract_address();
+        token::Client::new(&env, &token).transfer(&contract_address, &lp, &amount);
+
+        log!(
+            &env,
+            "Withdraw: {} tokens to {} for {} shares",
+            amount,
+            lp,
+            shares
+        );
+
+        Withdraw {
+            lp,
+            token,
+            version: EVENT_VERSION,
+            amount,
+            shares,
+        }
+        .publish(&env);
+
+        amount
+    }
+
+    /// Advance tokens from the vault to a recipient (borrower only)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `token` - The token to advance
+    /// * `to` - The recipient of the advance (e.g. a merchant)
+    /// * `amount` - Amount to advance
+    pub fn draw(env: Env, token: Address, to: Address, amount: i128) {
+        Self::borrower(&env).require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if amount <= 0 {
+            panic!("Draw amount must be positive");
+        }
+
+        let mut reserves = Self::load_reserves(&env, &token);
+        if amount > reserves.cash {
+            panic!("Insufficient pool liquidity");
+        }
+
+        reserves.cash -= amount;
+        reserves.borrowed += amount;
+        Self::save_reserves(&env, &token, &reserves);
+
+        let contract_address = env.current_contract_address();
+        token::Client::new(&env, &token).transfer(&contract_address, &to, &amount);
+
+        Draw {
+            token,
+            to,
+            version: EVENT_VERSION,
+            amount,
+            borrowed: reserves.borrowed,
+        }
+        .publish(&env);
+    }
+
+    /// Record a repayment to the vault (borrower only)
+    ///
+    /// The borrower transfers `principal + interest + reserve` to the vault
+    /// before calling this. Interest accrues to LPs; the reserve portion is
+    /// set aside to cover future write-offs.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `token` - The token repaid
+    /// * `principal` - Amount of advanced principal returned
+    /// * `interest` - BNPL fee income for LPs
+    /// * `reserve` - BNPL fee income for the bad-debt reserve
+    pub fn repay(env: Env, token: Address, principal: i128, interest: i128, reserve: i128) {
+        Self::borrower(&env).require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if principal < 0 || interest < 0 || reserve < 0 {
+            panic!("Repayment amounts must not be negative");
+        }
+        if principal + interest + reserve == 0 {
+            panic!("Repayment amount must be positive");
+        }
+
+        let mut reserves = Self::load_reserves(&env, &token);
+        if principal > reserves.borrowed {
+            panic!("Repayment amount exceeds borrowed");
+        }
+
+        reserves.cash += principal + interest;
+        reserves.borrowed -= principal;
+        reserves.reserve += reserve;
+        Self::save_reserves(&env, &token, &reserves);
+
+        Repay {
+            token,
+            version: EVENT_VERSION,
+            principal,
+            interest,
+            reserve,
+            borrowed: reserves.borrowed,
+        }
+        .publish(&env);
+    }
+
+    /// Write off defaulted principal (borrower only)
+    ///
+    /// The loss is covered from the bad-debt reserve first; whatever the
+    /// reserve can't cover is socialized across LP shares by shrinking the
+    /// vault's assets.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `token` - The token of the defaulted advances
+    /// * `amount` - Principal that will never be repaid
+    ///
+    /// # Returns
+    /// * The amount covered by the reserve
+    pub fn write_off(env: Env, token: Address, amount: i128) -> i128 {
+        Self::borrower(&env).require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if amount <= 0 {
+            panic!("Write-off amount must be positive");
+        }
+
+        let mut reserves = Self::load_reserves(&env, &token);
+        if amount > reserves.borrowed {
+            panic!("Write-off amount exceeds borrowed");
+        }
+
+        // Reserve tokens replace the lost principal as LP cash
+        let covered = amount.min(reserves.reserve);
+        reserves.reserve -= covered;
+        reserves.cash += covered;
+        reserves.borrowed -= amount;
+        Self::save_reserves(&env, &token, &reserves);
+
+        log!(
+            &env,
+            "Write-off: {} tokens, {} covered by reserve",
+            amount,
+            covered
+        );
+
+        WriteOff {
+            token,
+            version: EVENT_VERSION,
+            amount,
+            covered,
+            uncovered: amount - covered,
+        }
+        .publish(&env);
+
+        covered
+    }
+
+    /// Change the contract allowed to draw advances (admin only)
+    pub fn set_borrower(env: Env, borrower: Address) {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Contract not initialized");
+        admin.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        env.storage().instance().set(&DataKey::Borrower, &borrower);
+
+        BorrowerUpdated {
+            version: EVENT_VERSION,
+            admin,
+            borrower,
+        }
+        .publish(&env);
+    }
+
+    /// Get the vault accounting for a token
+    pub fn get_reserves(env: Env, token: Address) -> PoolReserves {
+        Self::load_reserves(&env, &token)
+    }
+
+    /// Get an LP's share balance for a token
+    pub fn get_shares(env: Env, lp: Address, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Shares(lp, token))
+            .unwrap_or(0)
+    }
+
+    /// Get the contract allowed to draw advances
+    pub fn get_borrower(env: Env) -> Address {
+        Self::borrower(&env)
+    }
+}
+
+// ============================================================================
+// INTERNAL HELPERS
+// ============================================================================
+
+impl LiquidityPoolContract {
+    /// Top up the contract instance TTL (admin and borrower)
+    fn extend_instance_ttl(env: &Env) {
+        env.storage()
+            .instance()
+            .extend_ttl(TTL_THRESHOLD_LEDGERS, THIRTY_DAYS_LEDGERS);
+    }
+
+    fn borrower(env: &Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Borrower)
+            .expect("Contract not initialized")
+    }
+
+    fn load_reserves(env: &Env, token: &Address) -> PoolReserves {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Reserves(token.clone()))
+            .unwrap_or(PoolReserves {
+                cash: 0,
+                borrowed: 0,
+                reserve: 0,
+                total_shares: 0,
+            })
+    }
+
+    fn save_reserves(env: &Env, token: &Address, reserves: &PoolReserves) {
+        let key = DataKey::Reserves(token.clone());
+        env.storage().persistent().set(&key, reserves);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, THIRTY_DAYS_LEDGERS, THIRTY_DAYS_LEDGERS);
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod test;
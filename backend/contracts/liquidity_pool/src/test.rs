@@ -0,0 +1,461 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::{
+    testutils::{storage::Instance as _, Address as _, Events, Ledger, LedgerInfo},
+    token::{StellarAssetClient, TokenClient},
+    Address, Env, Event,
+};
+
+/// Helper to create a test token and mint to an address
+fn create_token<'a>(env: &Env, admin: &Address) -> (TokenClient<'a>, StellarAssetClient<'a>) {
+    let contract_address = env.register_stellar_asset_contract_v2(admin.clone());
+    (
+        TokenClient::new(env, &contract_address.address()),
+        StellarAssetClient::new(env, &contract_address.address()),
+    )
+}
+
+/// Helper to set initial ledger state with proper protocol version
+fn setup_ledger(env: &Env) {
+    env.ledger().set(LedgerInfo {
+        timestamp: 1000,
+        protocol_version: 25,
+        sequence_number: 100,
+        network_id: Default::default(),
+        base_reserve: 10,
+        min_temp_entry_ttl: 100,
+        min_persistent_entry_ttl: 100,
+        max_entry_ttl: 10000,
+    });
+}
+
+/// Helper to setup the vault with a borrower
+fn setup_pool<'a>(env: &'a Env, borrower: &Address) -> (Address, LiquidityPoolContractClient<'a>) {
+    let admin = Address::generate(env);
+    let contract_id = env.register(LiquidityPoolContract, ());
+    let client = LiquidityPoolContractClient::new(env, &contract_id);
+    client.initialize(&admin, borrower);
+    (contract_id, client)
+}
+
+// ============================================================================
+// DEPOSIT / WITHDRAW TESTS
+// ============================================================================
+
+#[test]
+fn test_deposit_and_withdraw() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp, &1_000);
+
+    let (pool_id, pool) = setup_pool(&env, &borrower);
+
+    // First deposit mints shares one-to-one
+    assert_eq!(pool.deposit(&lp, &token, &1_000), 1_000);
+    assert_eq!(
+        env.events().all().filter_by_contract(&pool_id),
+        [Deposit {
+            lp: lp.clone(),
+            token: token.clone(),
+            version: EVENT_VERSION,
+            amount: 1_000,
+            shares: 1_000,
+        }
+        .to_xdr(&env, &pool_id)]
+    );
+    assert_eq!(pool.get_shares(&lp, &token), 1_000);
+    assert_eq!(token_client.balance(&pool_id), 1_000);
+
+    let reserves = pool.get_reserves(&token);
+    assert_eq!(reserves.cash, 1_000);
+    assert_eq!(reserves.borrowed, 0);
+    assert_eq!(reserves.total_shares, 1_000);
+
+    // Withdraw part, then the rest
+    assert_eq!(pool.withdraw(&lp, &token, &400), 400);
+    assert_eq!(pool.withdraw(&lp, &token, &600), 600);
+    assert_eq!(
+        env.events().all().filter_by_contract(&pool_id),
+        [Withdraw {
+            lp: lp.clone(),
+            token: token.clone(),
+            version: EVENT_VERSION,
+            amount: 600,
+            shares: 600,
+        }
+        .to_xdr(&env, &pool_id)]
+    );
+    assert_eq!(pool.get_shares(&lp, &token), 0);
+    assert_eq!(token_client.balance(&lp), 1_000);
+    assert_eq!(pool.get_reserves(&token).total_shares, 0);
+}
+
+#[test]
+fn test_shares_are_pro_rata() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp_a = Address::generate(&env);
+    let lp_b = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp_a, &1_000);
+    stellar_client.mint(&lp_b, &1_000);
+
+    let (pool_id, pool) = setup_pool(&env, &borrower);
+
+    pool.deposit(&lp_a, &token, &1_000);
+
+    // An advance and its repayment leave share prices unchanged
+    pool.draw(&token, &merchant, &300);
+    assert_eq!(token_client.balance(&merchant), 300);
+    stellar_client.mint(&pool_id, &300);
+    pool.repay(&token, &300, &0, &0);
+
+    // Second LP gets the same price
+    assert_eq!(pool.deposit(&lp_b, &token, &500), 500);
+
+    // Both LPs redeem proportionally to their shares
+    assert_eq!(pool.withdraw(&lp_a, &token, &1_000), 1_000);
+    assert_eq!(pool.withdraw(&lp_b, &token, &500), 500);
+}
+
+#[test]
+fn test_draw_and_repay_accounting() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp, &1_000);
+
+    let (pool_id, pool) = setup_pool(&env, &borrower);
+    pool.deposit(&lp, &token, &1_000);
+
+    pool.draw(&token, &merchant, &400);
+    let reserves = pool.get_reserves(&token);
+    assert_eq!(reserves.cash, 600);
+    assert_eq!(reserves.borrowed, 400);
+
+    // Borrower returns funds, then records the repayment
+    stellar_client.mint(&pool_id, &150);
+    pool.repay(&token, &150, &0, &0);
+    let reserves = pool.get_reserves(&token);
+    assert_eq!(reserves.cash, 750);
+    assert_eq!(reserves.borrowed, 250);
+    assert_eq!(token_client.balance(&pool_id), reserves.cash);
+
+    // Fee income is split between LPs and the bad-debt reserve
+    stellar_client.mint(&pool_id, &110);
+    pool.repay(&token, &100, &8, &2);
+    let reserves = pool.get_reserves(&token);
+    assert_eq!(reserves.cash, 858);
+    assert_eq!(reserves.borrowed, 150);
+    assert_eq!(reserves.reserve, 2);
+    assert_eq!(
+        token_client.balance(&pool_id),
+        reserves.cash + reserves.reserve
+    );
+}
+
+// ============================================================================
+// WRITE-OFF TESTS
+// ============================================================================
+
+#[test]
+fn test_write_off_covered_by_reserve() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp, &1_000);
+
+    let (pool_id, pool) = setup_pool(&env, &borrower);
+    pool.deposit(&lp, &token, &1_000);
+
+    // Build a reserve of 50 from fees, then lose 40 of principal
+    pool.draw(&token, &merchant, &100);
+    stellar_client.mint(&pool_id, &110);
+    pool.repay(&token, &60, &0, &50);
+    assert_eq!(pool.write_off(&token, &40), 40);
+
+    // LPs are made whole by the reserve
+    let reserves = pool.get_reserves(&token);
+    assert_eq!(reserves.cash, 1_000);
+    assert_eq!(reserves.borrowed, 0);
+    assert_eq!(reserves.reserve, 10);
+    assert_eq!(
+        token_client.balance(&pool_id),
+        reserves.cash + reserves.reserve
+    );
+    assert_eq!(pool.withdraw(&lp, &token, &1_000), 1_000);
+}
+
+#[test]
+fn test_write_off_socialized_across_shares() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp_a = Address::generate(&env);
+    let lp_b = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp_a, &600);
+    stellar_client.mint(&lp_b, &400);
+
+    let (pool_id, pool) = setup_pool(&env, &borrower);
+    pool.deposit(&lp_a, &token, &600);
+    pool.deposit(&lp_b, &token, &400);
+
+    // Reserve of 20 covers part of a 220 loss; LPs absorb the other 200
+    pool.draw(&token, &merchant, &220);
+    stellar_client.mint(&pool_id, &20);
+    pool.repay(&token, &0, &0, &20);
+    assert_eq!(pool.write_off(&token, &220), 20);
+
+    let reserves = pool.get_reserves(&token);
+    assert_eq!(reserves.cash, 800);
+    assert_eq!(reserves.borrowed, 0);
+    assert_eq!(reserves.reserve, 0);
+    assert_eq!(reserves.total_shares, 1_000);
+    assert_eq!(
+        token_client.balance(&pool_id),
+        reserves.cash + reserves.reserve
+    );
+
+    // Each LP loses 20% of their position
+    assert_eq!(pool.withdraw(&lp_a, &token, &600), 480);
+    assert_eq!(pool.withdraw(&lp_b, &token, &400), 320);
+}
+
+#[test]
+#[should_panic(expected = "Vault has no assets backing its shares")]
+fn test_deposit_rejected_after_total_loss() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp_a = Address::generate(&env);
+    let lp_b = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp_a, &100);
+    stellar_client.mint(&lp_b, &100);
+
+    let (_, pool) = setup_pool(&env, &borrower);
+    pool.deposit(&lp_a, &token, &100);
+
+    // Everything is lent out and lost; the old shares are left unbacked
+    pool.draw(&token, &merchant, &100);
+    assert_eq!(pool.write_off(&token, &100), 0);
+    let reserves = pool.get_reserves(&token);
+    assert_eq!(reserves.cash + reserves.borrowed, 0);
+    assert_eq!(reserves.total_shares, 100);
+
+    pool.deposit(&lp_b, &token, &100);
+}
+
+#[test]
+fn test_leftover_assets_go_to_reserve_not_next_depositor() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp_a = Address::generate(&env);
+    let lp_b = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp_a, &100);
+    stellar_client.mint(&lp_b, &100);
+
+    let (pool_id, pool) = setup_pool(&env, &borrower);
+    pool.deposit(&lp_a, &token, &100);
+
+    // The advance is lost and the only LP leaves with nothing
+    pool.draw(&token, &merchant, &100);
+    pool.write_off(&token, &100);
+    assert_eq!(pool.withdraw(&lp_a, &token, &100), 0);
+
+    // Late fee income arrives while nobody holds shares
+    stellar_client.mint(&pool_id, &5);
+    pool.repay(&token, &0, &5, &0);
+    assert_eq!(pool.get_reserves(&token).cash, 5);
+
+    // The next depositor gets shares for their own deposit only
+    assert_eq!(pool.deposit(&lp_b, &token, &100), 100);
+    let reserves = pool.get_reserves(&token);
+    assert_eq!(reserves.cash, 100);
+    assert_eq!(reserves.reserve, 5);
+    assert_eq!(pool.withdraw(&lp_b, &token, &100), 100);
+}
+
+#[test]
+#[should_panic(expected = "Insufficient pool liquidity")]
+fn test_withdraw_limited_to_cash() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp, &1_000);
+
+    let (_, pool) = setup_pool(&env, &borrower);
+    pool.deposit(&lp, &token, &1_000);
+    pool.draw(&token, &merchant, &500);
+
+    // Half of the vault is lent out, so a full exit must fail
+    pool.withdraw(&lp, &token, &1_000);
+}
+
+#[test]
+#[should_panic(expected = "Insufficient pool liquidity")]
+fn test_draw_exceeding_cash() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp, &100);
+
+    let (_, pool) = setup_pool(&env, &borrower);
+    pool.deposit(&lp, &token, &100);
+    pool.draw(&token, &merchant, &101);
+}
+
+#[test]
+fn test_set_borrower() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let new_borrower = Address::generate(&env);
+    let pool_id = env.register(LiquidityPoolContract, ());
+    let pool = LiquidityPoolContractClient::new(&env, &pool_id);
+    pool.initialize(&admin, &borrower);
+
+    pool.set_borrower(&new_borrower);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(
+        env.events().all().filter_by_contract(&pool_id),
+        [BorrowerUpdated {
+            version: EVENT_VERSION,
+            admin: admin.clone(),
+            borrower: new_borrower.clone(),
+        }
+        .to_xdr(&env, &pool_id)]
+    );
+    assert_eq!(pool.get_borrower(), new_borrower);
+}
+
+#[test]
+fn test_draw_requires_borrower_auth() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp, &100);
+
+    let (pool_id, pool) = setup_pool(&env, &borrower);
+    pool.deposit(&lp, &token, &100);
+    pool.draw(&token, &merchant, &50);
+
+    // The draw was authorized by the borrower, not by anyone else
+    let auths = env.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].0, borrower);
+    assert_eq!(pool.get_borrower(), borrower);
+    assert_eq!(token_client.balance(&pool_id), 50);
+}
+
+// ============================================================================
+// TTL TESTS
+// ============================================================================
+
+#[test]
+fn test_vault_activity_keeps_the_instance_live() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let borrower = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    stellar_client.mint(&lp, &1_000);
+
+    let (pool_id, pool) = setup_pool(&env, &borrower);
+
+    // Three times the maximum entry TTL passes; vault activity in between
+    // keeps the instance (borrower and admin) live
+    for _ in 0..5 {
+        env.ledger().with_mut(|li| li.sequence_number += 6_000);
+        assert!(env.as_contract(&pool_id, || env.storage().instance().get_ttl()) > 0);
+        pool.deposit(&lp, &token, &100);
+        pool.draw(&token, &merchant, &10);
+    }
+    assert_eq!(pool.get_borrower(), borrower);
+}
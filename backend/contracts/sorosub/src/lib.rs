@@ -1,7 +1,8 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, log, symbol_short, token, Address, Env, Symbol,
+    contract, contractclient, contracterror, contractevent, contractimpl, contracttype, log,
+    panic_with_error, token, Address, BytesN, Env, FromVal, Map, Symbol, Val, Vec,
 };
 
 // ============================================================================
@@ -16,26 +17,289 @@ pub struct SubscriptionKey {
     pub merchant: Address,
 }
 
+/// Lifecycle state of a subscription
+///
+/// Allowed transitions (see `can_transition_to`):
+/// * Trialing -> Active, PastDue, Paused
+/// * Active -> PastDue, Paused
+/// * PastDue -> Active, Suspended
+/// * Paused, Suspended -> Active
+/// * any live state -> Completed (by the merchant) or Cancelled (by the subscriber)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum SubscriptionStatus {
+    Trialing,  // Free trial; the first payment is due when it ends
+    Active,    // Billing normally
+    Paused,    // Paused by the subscriber; nothing can be collected
+    PastDue,   // A due payment couldn't be funded; collection is retried
+    Suspended, // Still unpaid after the grace period; the subscriber must resume
+    Completed, // Ended by the merchant (terminal)
+    Cancelled, // Ended by the subscriber (terminal)
+}
+
+impl SubscriptionStatus {
+    /// Whether the state machine allows moving from `self` to `next`
+    pub fn can_transition_to(self, next: SubscriptionStatus) -> bool {
+        use SubscriptionStatus::*;
+        match (self, next) {
+            (Completed | Cancelled, _) => false,
+            (_, Completed | Cancelled) => true,
+            (Trialing, Active | PastDue | Paused) => true,
+            (Active, PastDue | Paused) => true,
+            (PastDue, Active | Suspended) => true,
+            (Paused | Suspended, Active) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the subscription is still running (not completed or cancelled)
+    pub fn is_live(self) -> bool {
+        !matches!(
+            self,
+            SubscriptionStatus::Completed | SubscriptionStatus::Cancelled
+        )
+    }
+
+    /// Whether payments may be collected in this state
+    pub fn is_collectible(self) -> bool {
+        matches!(
+            self,
+            SubscriptionStatus::Trialing | SubscriptionStatus::Active | SubscriptionStatus::PastDue
+        )
+    }
+}
+
 /// The subscription data stored on-chain
 #[derive(Clone)]
 #[contracttype]
 pub struct Subscription {
-    pub subscriber: Address,      // The subscriber's address
-    pub merchant: Address,        // The merchant receiving payments
-    pub token: Address,           // Token contract address (e.g., USDC)
-    pub amount: i128,             // Amount per payment (in token's smallest unit)
-    pub interval: u64,            // Seconds between payments
-    pub last_payment_time: u64,   // Timestamp of last payment
-    pub is_active: bool,          // Whether subscription is active
-    pub credit_score: u32,        // Credit score (starts at 0, +10 per successful payment)
+    pub subscriber: Address, // The subscriber's address (who the subscription is for)
+    pub payer: Address,      // Address paying for it (the subscriber unless gifted)
+    pub merchant: Address,   // The merchant receiving payments
+    pub token: Address,      // Token contract address (e.g., USDC)
+    pub amount: i128,        // Amount per payment (in token's smallest unit)
+    pub interval: u64,       // Seconds between payments
+    pub starts_at: u64,      // When the first payment is due (end of any trial)
+    pub last_payment_time: u64, // Timestamp of last payment
+    pub status: SubscriptionStatus, // Lifecycle state
+    pub credit_score: u32,   // Credit score (starts at 0, +10 per successful payment)
+}
+
+/// Seats of a team or family subscription, billed per seat to its owner
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SeatPlan {
+    pub price_per_seat: i128, // Amount charged per seat in the current period
+    pub next_price_per_seat: i128, // Price per seat from the next collection on
+    pub seats: u32,           // Seats billed in the current period
+    pub next_seats: u32,      // Seats billed from the next collection on
+    pub members: Vec<Address>, // Addresses sharing the owner's access (at most `seats`)
+}
+
+/// Compact summary of a subscriber's finished subscriptions, kept for
+/// credit history once the full records are purged or replaced
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct CreditHistory {
+    pub closed_subscriptions: u32, // Finished subscriptions folded into the summary
+    pub total_credit_score: u32,   // Credit score accumulated across them
+    pub best_credit_score: u32,    // Highest credit score any of them reached
+    pub last_payment_time: u64,    // Most recent payment made under any of them
+}
+
+/// How a collected payment was funded
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum FundingSource {
+    Balance,   // Pulled from the subscriber's balance
+    Bnpl(u32), // Fronted by the liquidity pool as the BNPL advance with this id
+}
+
+/// Proof of a single collected payment
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Receipt {
+    pub id: u32,               // Sequential id per subscriber/merchant pair (starts at 0)
+    pub token: Address,        // Token paid
+    pub amount: i128,          // Amount paid for the period (including the protocol fee)
+    pub fee: i128,             // Protocol fee taken out of it
+    pub timestamp: u64,        // When the payment was collected
+    pub source: FundingSource, // Where the funds came from
+    pub refunded: i128,        // Portion refunded by the merchant so far
+}
+
+impl Receipt {
+    /// Amount the merchant can still refund; the protocol fee is not refundable
+    pub fn refundable(&self) -> i128 {
+        self.amount - self.fee - self.refunded
+    }
+}
+
+/// One payee's share of a merchant's revenue
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Split {
+    pub recipient: Address, // Address receiving this share
+    pub share_bps: u32,     // Share of the merchant's revenue in basis points
+}
+
+/// Collected funds waiting out a merchant's settlement delay
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Hold {
+    pub subscriber: Address, // Subscriber who paid
+    pub receipt_id: u32,     // Receipt of the held payment
+    pub collected_at: u64,   // When it was collected (tells reused receipt ids apart)
+    pub amount: i128,        // Amount still held (refunds and disputes draw it down)
+    pub release_at: u64,     // When the merchant can withdraw it
+}
+
+/// A merchant's unsettled funds in one token
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct Settlement {
+    pub unsettled: i128,   // Total still held for the merchant
+    pub next_release: u32, // Oldest hold not yet withdrawn
+    pub next_hold: u32,    // Id the next hold gets
+}
+
+/// State of a payment dispute
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum DisputeStatus {
+    Open,                  // Waiting for an arbiter
+    ResolvedForSubscriber, // The subscriber won; escrowed funds were refunded
+    ResolvedForMerchant,   // The merchant won; escrowed funds were released
+}
+
+/// A subscriber's dispute against a collected payment
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Dispute {
+    pub receipt_id: u32,       // Receipt of the disputed payment
+    pub token: Address,        // Token of the disputed payment
+    pub source: FundingSource, // How the disputed payment was funded
+    pub amount: i128,          // Amount in dispute
+    pub escrowed: i128,        // Amount the merchant holds in escrow for it
+    pub held: i128,            // Part of the escrow taken from the merchant's unsettled funds
+    pub opened_at: u64,        // When the dispute was opened
+    pub status: DisputeStatus, // Open or how it was resolved
+}
+
+/// A merchant's dispute track record
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct MerchantDisputes {
+    pub won: u32,  // Disputes resolved in the merchant's favor
+    pub lost: u32, // Disputes resolved in the subscriber's favor
+}
+
+/// Positions of a live subscription in the subscriber and merchant indexes
+#[derive(Clone)]
+#[contracttype]
+pub struct IndexPosition {
+    pub subscriber_pos: u32, // Slot in the subscriber's index
+    pub merchant_pos: u32,   // Slot in the merchant's index
 }
 
 /// Tracks user debt from BNPL (Buy Now Pay Later)
 #[derive(Clone)]
 #[contracttype]
 pub struct UserDebt {
-    pub amount: i128,    // Total outstanding BNPL debt
-    pub token: Address,  // Token owed
+    pub amount: i128,       // Total outstanding BNPL debt
+    pub token: Address,     // Token owed
+    pub advance_count: u32, // Number of advances ever recorded (next advance id)
+    pub next_unpaid: u32,   // Id of the oldest advance not yet fully repaid
+}
+
+/// A single BNPL advance fronted by the liquidity pool
+#[derive(Clone)]
+#[contracttype]
+pub struct BnplAdvance {
+    pub id: u32,           // Sequential id per user (starts at 0)
+    pub merchant: Address, // Merchant that was paid (this contract for debt carried over from v1)
+    pub token: Address,    // Token advanced
+    pub amount: i128,      // Amount fronted by the pool
+    pub fee: i128,         // BNPL fee owed on top of the amount
+    pub timestamp: u64,    // When the advance was made
+    pub repaid: i128,      // Portion repaid so far (principal first, then fee)
+    pub written_off: bool, // Whether the unpaid remainder was written off as bad debt
+    pub pool: Address,     // Vault that funded it (the v1 pool for debt carried over from v1)
+}
+
+/// Liquidity pool health for a token, as seen by SoroSub
+#[derive(Clone)]
+#[contracttype]
+pub struct PoolHealth {
+    pub cash: i128,               // Idle vault reserves available for advances
+    pub outstanding_debt: i128, // BNPL principal owed to the vault (its `borrowed`, fees excluded)
+    pub utilization_bps: u32,   // outstanding_debt / (cash + outstanding_debt)
+    pub max_utilization_bps: u32, // Utilization above which advances are refused
+}
+
+/// Admin-tunable risk and credit parameters
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Config {
+    pub credit_score_increment: u32, // Credit score gained per successful payment
+    pub bnpl_min_credit_score: u32,  // Credit score that must be exceeded for BNPL
+    pub max_utilization_bps: u32,    // Maximum share of pool funds that may be lent out
+    pub bnpl_fee_bps: u32,           // BNPL fee charged on top of each advance
+    pub reserve_share_bps: u32,      // Slice of BNPL fees sent to the bad-debt reserve
+    pub default_period: u64,         // Seconds after which an unpaid advance is in default
+}
+
+/// An admin handover waiting for the new admin to accept
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PendingAdmin {
+    pub address: Address, // Proposed new admin
+    pub expires_at: u64,  // Timestamp after which the proposal can no longer be accepted
+}
+
+/// Emergency pause switches. Cancelling and repaying debt are never paused
+/// so users can always exit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PauseFlags {
+    pub subscriptions: bool, // Block create_subscription
+    pub collections: bool,   // Block collect_payment
+    pub bnpl: bool,          // Block BNPL advances from the liquidity pool
+}
+
+/// A risk or fee parameter update waiting out the timelock
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum ParamChange {
+    Config(Config),                    // Replace the risk and credit parameters
+    LiquidityPool(Address),            // Point BNPL funding at a different vault
+    ProtocolFee(u32),                  // Protocol fee on collections in basis points
+    Treasury(Address),                 // Address receiving protocol fees
+    MerchantFee(Address, Option<u32>), // Set or clear a merchant's protocol fee override
+    SettlementDelay(Address, u64),     // How long a merchant's collected funds are held
+}
+
+/// A queued parameter change
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PendingChange {
+    pub id: u32,             // Sequential id of the queued change
+    pub change: ParamChange, // What will be changed
+    pub proposer: Address,   // Operator that queued the change
+    pub execute_after: u64,  // Timestamp from which anyone may execute it
+}
+
+/// Operator roles. The admin implicitly holds every role; the admin role
+/// itself changes hands through `propose_admin` / `accept_admin`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Role {
+    Admin,            // Upgrades, migrations and role management
+    Pauser,           // Trip the emergency pause switches
+    RiskManager,      // Risk parameters and bad-debt write-offs
+    Treasurer,        // Liquidity pool funding and protocol fees
+    MerchantVerifier, // Merchant verification registry
+    Arbiter,          // Resolve payment disputes for merchants without their own arbiter
 }
 
 /// Storage keys for the contract
@@ -43,36 +307,672 @@ pub struct UserDebt {
 #[contracttype]
 pub enum DataKey {
     Subscription(SubscriptionKey),
+    CreditHistory(Address), // Summary of a subscriber's finished subscriptions
     UserDebt(Address),      // Track user debts for BNPL
-    LiquidityPool,          // Simulated liquidity pool address
+    Advance(Address, u32),  // Itemized BNPL advance (user, advance id)
+    LiquidityPool,          // Liquidity pool vault contract address
+    LegacyPool,             // Pool owed the debt carried over from schema v1
+    AutoRepay(Address),     // Opt-in auto-repay portion (basis points of debt)
+    TotalDebt(Address, Address), // Outstanding BNPL principal per vault and token
+    Defaulted(Address),     // User had BNPL debt written off (no further advances)
+    Config,                 // Risk and credit parameters (defaults until updated)
     Admin,                  // Admin address for initialization
+    PendingAdmin,           // Proposed admin awaiting acceptance
+    Role(Role, Address),    // Operator role granted to an address
+    VerifiedMerchant(Address), // Merchant vetted by a merchant verifier
+    SubscriberIndex(Address, u32), // Merchant at a position in a subscriber's index
+    SubscriberIndexLen(Address), // Number of live subscriptions of a subscriber
+    MerchantIndex(Address, u32), // Subscriber at a position in a merchant's index
+    MerchantIndexLen(Address), // Number of live subscriptions paying a merchant
+    IndexPosition(SubscriptionKey), // Where a live subscription sits in both indexes
+    Receipt(SubscriptionKey, u32), // Payment receipt of a subscription by id
+    ReceiptCount(SubscriptionKey), // Receipts ever recorded for a subscription (next id)
+    ReceiptStart(SubscriptionKey), // Oldest receipt still stored for a subscription
+    Dispute(SubscriptionKey, u32), // Dispute against a payment by receipt id
+    OpenDisputes(SubscriptionKey), // Number of open disputes against a subscription
+    MerchantArbiter(Address), // Arbiter resolving a merchant's disputes
+    Unescrowed(Address),    // Open disputes a merchant hasn't fully escrowed
+    MerchantDisputes(Address), // Disputes a merchant has won and lost
+    SettlementDelay(Address), // How long a merchant's collected funds are held
+    Settlement(Address, Address), // A merchant's unsettled funds by token
+    Hold(Address, Address, u32), // Held payment by merchant, token and hold id
+    HoldOf(SubscriptionKey, u32, u64), // Hold id of a receipt by id and collection time
+    Treasury,               // Address receiving protocol fees
+    ProtocolFee,            // Protocol fee on collections in basis points
+    MerchantFee(Address),   // Protocol fee override for a merchant in basis points
+    ProtocolFees(Address),  // Protocol fees collected so far per token
+    RevenueSplit(Address),  // How a merchant's revenue is split between payees
+    SeatPlan(SubscriptionKey), // Seats and members of a team subscription
+    Membership(Address, Address), // Owner of the team plan a member belongs to (member, merchant)
+    Pause,                  // Emergency pause switches
+    ParamChange(u32),       // Queued parameter change by id
+    NextParamChange,        // Id of the next queued parameter change
+    SchemaVersion,          // Storage layout version (absent means v1)
     Initialized,            // Whether contract is initialized
 }
 
+// ============================================================================
+// LEGACY STORAGE TYPES
+// ============================================================================
+
+/// `Subscription` as stored by schema v4, paid for by the subscriber
+#[derive(Clone)]
+#[contracttype]
+pub struct SubscriptionV4 {
+    pub subscriber: Address,        // The subscriber's address
+    pub merchant: Address,          // The merchant receiving payments
+    pub token: Address,             // Token contract address (e.g., USDC)
+    pub amount: i128,               // Amount per payment (in token's smallest unit)
+    pub interval: u64,              // Seconds between payments
+    pub starts_at: u64,             // When the first payment is due (end of any trial)
+    pub last_payment_time: u64,     // Timestamp of last payment
+    pub status: SubscriptionStatus, // Lifecycle state
+    pub credit_score: u32,          // Credit score (starts at 0, +10 per successful payment)
+}
+
+/// `Subscription` as stored before schema v4, with a plain active flag
+#[derive(Clone)]
+#[contracttype]
+pub struct SubscriptionV1 {
+    pub subscriber: Address,    // The subscriber's address
+    pub merchant: Address,      // The merchant receiving payments
+    pub token: Address,         // Token contract address (e.g., USDC)
+    pub amount: i128,           // Amount per payment (in token's smallest unit)
+    pub interval: u64,          // Seconds between payments
+    pub last_payment_time: u64, // Timestamp of last payment
+    pub is_active: bool,        // Whether subscription is active
+    pub credit_score: u32,      // Credit score (starts at 0, +10 per successful payment)
+}
+
+/// `UserDebt` as stored by schema v1, before advances were itemized
+#[derive(Clone)]
+#[contracttype]
+pub struct UserDebtV1 {
+    pub amount: i128,   // Total outstanding BNPL debt
+    pub token: Address, // Token owed
+}
+
+// ============================================================================
+// LIQUIDITY POOL INTERFACE
+// ============================================================================
+
+/// The subset of the liquidity pool vault (`contracts/liquidity_pool`) used
+/// to fund BNPL advances. SoroSub is registered as the vault's borrower.
+#[contractclient(name = "LiquidityPoolClient")]
+pub trait LiquidityPoolInterface {
+    /// Advance `amount` of `token` from the vault to `to`
+    fn draw(env: Env, token: Address, to: Address, amount: i128);
+
+    /// Record a repayment: `principal` returned, plus fee income split
+    /// between LPs (`interest`) and the bad-debt reserve (`reserve`)
+    fn repay(env: Env, token: Address, principal: i128, interest: i128, reserve: i128);
+
+    /// Write off defaulted principal, returning the amount the reserve covered
+    fn write_off(env: Env, token: Address, amount: i128) -> i128;
+
+    /// Get the vault accounting for `token`
+    fn get_reserves(env: Env, token: Address) -> PoolReserves;
+}
+
+/// Vault accounting returned by `LiquidityPoolInterface::get_reserves`
+#[derive(Clone)]
+#[contracttype]
+pub struct PoolReserves {
+    pub cash: i128,
+    pub borrowed: i128,
+    pub reserve: i128,
+    pub total_shares: i128,
+}
+
 // ============================================================================
 // EVENTS
 // ============================================================================
 
-// Event symbols (max 9 chars for symbol_short!)
-const SUB_CREATED: Symbol = symbol_short!("sub_new");
-const SUB_CANCELLED: Symbol = symbol_short!("sub_end");
-const PAYMENT: Symbol = symbol_short!("payment");
-const BNPL_TRIGGER: Symbol = symbol_short!("bnpl");
-const DEBT_REPAID: Symbol = symbol_short!("repaid");
+// Every payload carries `version`, bumped whenever that payload's fields
+// change, so indexers can decode events emitted before and after upgrades.
+
+/// Version carried by every event payload
+pub const EVENT_VERSION: u32 = 1;
+
+/// A subscription was created
+#[contractevent(topics = ["sub_new"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionCreated {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub token: Address, // Token billed
+    pub amount: i128,   // Amount per payment
+    pub interval: u64,  // Seconds between payments
+}
+
+/// A payment was collected from the subscriber's balance
+#[contractevent(topics = ["payment"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaymentCollected {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub token: Address,    // Token paid
+    pub amount: i128,      // Amount paid to the merchant
+    pub credit_score: u32, // Subscription credit score afterwards
+}
+
+/// The liquidity pool paid the merchant as a BNPL advance
+#[contractevent(topics = ["bnpl"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BnplAdvanced {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub token: Address,   // Token advanced
+    pub amount: i128,     // Amount paid to the merchant
+    pub fee: i128,        // BNPL fee owed on top
+    pub advance_id: u32,  // Id of the recorded advance
+    pub total_debt: i128, // Subscriber's outstanding debt afterwards
+}
+
+/// A subscription was cancelled
+#[contractevent(topics = ["sub_end"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionCancelled {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub token: Address,         // Token that was billed
+    pub last_payment_time: u64, // Timestamp of the last payment
+    pub credit_score: u32,      // Credit score the subscription reached
+}
+
+/// A subscription moved to a new lifecycle state (cancellations are
+/// reported by `SubscriptionCancelled`)
+#[contractevent(topics = ["status"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StatusChanged {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub from: SubscriptionStatus, // Previous state
+    pub to: SubscriptionStatus,   // New state
+}
+
+/// A finished subscription was purged into its subscriber's credit history
+#[contractevent(topics = ["sub_purge"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionPurged {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub credit_score: u32, // Credit score folded into the history
+}
+
+/// A merchant refunded (part of) a collected payment
+#[contractevent(topics = ["refund"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Refunded {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub receipt_id: u32, // Receipt of the refunded payment
+    pub token: Address,  // Token refunded
+    pub amount: i128,    // Amount refunded
+    pub to_debt: i128,   // Portion applied to the subscriber's BNPL debt
+}
+
+/// An owner gave a member a seat on its team subscription
+#[contractevent(topics = ["member_add"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MemberAdded {
+    #[topic]
+    pub owner: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub member: Address, // New member
+}
+
+/// An owner took a seat back from a member
+#[contractevent(topics = ["member_rm"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MemberRemoved {
+    #[topic]
+    pub owner: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub member: Address, // Removed member
+}
+
+/// An owner changed the seat count of its team subscription
+#[contractevent(topics = ["seats"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SeatsScheduled {
+    #[topic]
+    pub owner: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub seats: u32,        // Seats billed from the next collection on
+    pub next_amount: i128, // Amount the next collection will charge
+}
+
+/// A merchant changed the price per seat of a team subscription
+#[contractevent(topics = ["seat_price"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SeatPriceScheduled {
+    #[topic]
+    pub owner: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub price_per_seat: i128, // Price per seat from the next collection on
+    pub next_amount: i128,    // Amount the next collection will charge
+}
+
+/// A subscription was created with a payer other than its subscriber
+#[contractevent(topics = ["gift"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SubscriptionGifted {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub payer: Address, // Address paying for the subscription
+}
+
+/// A subscriber disputed a payment
+#[contractevent(topics = ["dispute"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DisputeOpened {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub receipt_id: u32, // Receipt of the disputed payment
+    pub amount: i128,    // Amount in dispute
+}
+
+/// A merchant placed disputed funds in escrow
+#[contractevent(topics = ["escrow"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DisputeEscrowed {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub receipt_id: u32, // Receipt of the disputed payment
+    pub escrowed: i128,  // Total now held in escrow
+}
+
+/// An arbiter resolved a dispute
+#[contractevent(topics = ["resolved"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DisputeResolved {
+    #[topic]
+    pub subscriber: Address,
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub receipt_id: u32,       // Receipt of the disputed payment
+    pub arbiter: Address,      // Arbiter that decided
+    pub status: DisputeStatus, // Which side won
+    pub refunded: i128,        // Escrowed funds refunded to the subscriber
+}
+
+/// A protocol fee was taken out of a collection
+#[contractevent(topics = ["fee"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProtocolFeeCharged {
+    #[topic]
+    pub merchant: Address,
+    #[topic]
+    pub token: Address,
+    pub version: u32,
+    pub gross: i128,       // Amount paid for the period
+    pub fee: i128,         // Portion sent to the treasury
+    pub net: i128,         // Portion paid to the merchant
+    pub treasury: Address, // Where the fee went
+}
+
+/// A merchant changed how its revenue is split
+#[contractevent(topics = ["split"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RevenueSplitUpdated {
+    #[topic]
+    pub merchant: Address,
+    pub version: u32,
+    pub splits: Vec<Split>, // New split table (empty pays the merchant directly)
+}
+
+/// A merchant withdrew funds whose settlement delay had passed
+#[contractevent(topics = ["settled"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Settled {
+    #[topic]
+    pub merchant: Address,
+    #[topic]
+    pub token: Address,
+    pub version: u32,
+    pub amount: i128, // Amount withdrawn
+    pub holds: u32,   // Number of holds released
+}
+
+/// BNPL debt was repaid
+#[contractevent(topics = ["repaid"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DebtRepaid {
+    #[topic]
+    pub user: Address,
+    pub version: u32,
+    pub token: Address,  // Token repaid
+    pub amount: i128,    // Amount repaid
+    pub remaining: i128, // Outstanding debt afterwards
+}
+
+/// Defaulted BNPL debt was written off
+#[contractevent(topics = ["bad_debt"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DebtWrittenOff {
+    #[topic]
+    pub user: Address,
+    #[topic]
+    pub token: Address,
+    pub version: u32,
+    pub written_off: i128, // Outstanding debt cleared (principal and fees)
+    pub principal: i128,   // Unpaid principal written off in the vault
+    pub covered: i128,     // Portion covered by the bad-debt reserve
+    pub uncovered: i128,   // Portion absorbed by liquidity providers
+}
+
+/// An admin handover was proposed
+#[contractevent(topics = ["admin", "propose"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdminProposed {
+    pub version: u32,
+    pub admin: Address,     // Current admin
+    pub new_admin: Address, // Proposed admin
+    pub expires_at: u64,    // Timestamp after which the proposal lapses
+}
+
+/// An admin handover was accepted
+#[contractevent(topics = ["admin", "accept"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdminAccepted {
+    pub version: u32,
+    pub previous_admin: Address, // Admin handing over
+    pub new_admin: Address,      // Admin taking over
+}
+
+/// A pending admin handover was withdrawn
+#[contractevent(topics = ["admin", "cancel"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdminProposalCancelled {
+    pub version: u32,
+    pub admin: Address,    // Current admin
+    pub proposed: Address, // Address that had been proposed
+}
+
+/// An operator role was granted
+#[contractevent(topics = ["admin", "grant"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoleGranted {
+    pub version: u32,
+    pub admin: Address,   // Admin granting the role
+    pub role: Role,       // Role granted
+    pub account: Address, // Address receiving it
+}
+
+/// An operator role was revoked
+#[contractevent(topics = ["admin", "revoke"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoleRevoked {
+    pub version: u32,
+    pub admin: Address,   // Admin revoking the role
+    pub role: Role,       // Role revoked
+    pub account: Address, // Address losing it
+}
+
+/// The emergency pause switches changed
+#[contractevent(topics = ["admin", "pause"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PauseUpdated {
+    pub version: u32,
+    pub caller: Address,   // Pauser or admin
+    pub flags: PauseFlags, // New switches
+}
+
+/// A parameter change was queued behind the timelock
+#[contractevent(topics = ["admin", "queue"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParamChangeQueued {
+    pub version: u32,
+    pub caller: Address,     // Operator that queued it
+    pub id: u32,             // Id of the queued change
+    pub change: ParamChange, // What will be changed
+    pub execute_after: u64,  // Timestamp from which it may be executed
+}
+
+/// A queued parameter change was cancelled
+#[contractevent(topics = ["admin", "unqueue"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParamChangeCancelled {
+    pub version: u32,
+    pub caller: Address, // Operator that cancelled it
+    pub id: u32,         // Id of the cancelled change
+}
+
+/// The risk and credit parameters were replaced
+#[contractevent(topics = ["admin", "config"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigUpdated {
+    pub version: u32,
+    pub proposer: Address, // Operator that queued the change
+    pub config: Config,    // New parameters
+}
+
+/// BNPL funding was pointed at a different vault
+#[contractevent(topics = ["admin", "set_pool"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LiquidityPoolUpdated {
+    pub version: u32,
+    pub proposer: Address,       // Operator that queued the change
+    pub liquidity_pool: Address, // New vault
+}
+
+/// A merchant's verification status was recorded
+#[contractevent(topics = ["admin", "verify"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantVerified {
+    pub version: u32,
+    pub caller: Address,   // Merchant verifier
+    pub merchant: Address, // Merchant concerned
+    pub verified: bool,    // New status
+}
+
+/// The treasury receiving protocol fees was changed
+#[contractevent(topics = ["admin", "treasury"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TreasuryUpdated {
+    pub version: u32,
+    pub proposer: Address, // Admin that queued the change
+    pub treasury: Address, // New treasury
+}
+
+/// The protocol fee was changed
+#[contractevent(topics = ["admin", "fee"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProtocolFeeUpdated {
+    pub version: u32,
+    pub proposer: Address, // Operator that queued the change
+    pub fee_bps: u32,      // New protocol fee in basis points
+}
+
+/// A merchant's protocol fee override was set or cleared
+#[contractevent(topics = ["admin", "merchant_fee"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantFeeUpdated {
+    pub version: u32,
+    pub proposer: Address,    // Treasurer that queued the change
+    pub merchant: Address,    // Merchant concerned
+    pub fee_bps: Option<u32>, // New override (None falls back to the protocol fee)
+}
+
+/// A merchant's settlement delay was changed
+#[contractevent(topics = ["admin", "settle_delay"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SettlementDelayUpdated {
+    pub version: u32,
+    pub proposer: Address, // Merchant verifier that queued the change
+    pub merchant: Address, // Merchant concerned
+    pub delay: u64,        // New hold period in seconds (0 pays out directly)
+}
+
+/// A merchant's dedicated arbiter was set or cleared
+#[contractevent(topics = ["admin", "arbiter"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantArbiterUpdated {
+    pub version: u32,
+    pub caller: Address,          // Merchant verifier
+    pub merchant: Address,        // Merchant concerned
+    pub arbiter: Option<Address>, // New arbiter (None falls back to global arbiters)
+}
+
+/// The contract code was upgraded
+#[contractevent(topics = ["admin", "upgrade"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Upgraded {
+    pub version: u32,
+    pub admin: Address,            // Admin performing the upgrade
+    pub new_wasm_hash: BytesN<32>, // Hash of the new code
+}
+
+/// Storage was migrated to the current schema
+#[contractevent(topics = ["admin", "migrate"])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Migrated {
+    pub version: u32,
+    pub admin: Address,    // Admin running the migration
+    pub from_version: u32, // Schema version before
+    pub to_version: u32,   // Schema version after
+    pub migrated: u32,     // Records converted in this batch
+}
+
+// ============================================================================
+// ERRORS
+// ============================================================================
+
+/// Subscription lifecycle errors
+#[contracterror]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum SubscriptionError {
+    InvalidTransition = 1, // The subscription's status doesn't allow this change
+    NotCollectible = 2,    // Payments can't be collected in the subscription's status
+    AlreadyExists = 3,     // A live subscription to this merchant already exists
+}
 
 // ============================================================================
 // CONSTANTS
 // ============================================================================
 
-/// Credit score increment per successful payment
+/// Default credit score increment per successful payment
 const CREDIT_SCORE_INCREMENT: u32 = 10;
 
-/// Minimum credit score required for BNPL
+/// Default minimum credit score required for BNPL
 const BNPL_MIN_CREDIT_SCORE: u32 = 50;
 
+/// Maximum number of records returned by a paginated view
+const MAX_PAGE_SIZE: u32 = 50;
+
+/// Denominator for basis-point values (10_000 = 100%)
+const BPS_DENOMINATOR: u32 = 10_000;
+
+/// Default maximum share of pool funds that may be lent out (80%)
+const MAX_UTILIZATION_BPS: u32 = 8_000;
+
+/// Default BNPL fee charged on top of each advance (1%)
+const BNPL_FEE_BPS: u32 = 100;
+
+/// Default slice of BNPL fees set aside in the pool's bad-debt reserve (20%)
+const RESERVE_SHARE_BPS: u32 = 2_000;
+
+/// Default time after which an unpaid advance is considered defaulted (90 days)
+const DEFAULT_PERIOD: u64 = 90 * 24 * 60 * 60;
+
+/// Delay before a queued parameter change can be executed (48 hours)
+const PARAM_CHANGE_DELAY: u64 = 48 * 60 * 60;
+
+/// How long a proposed admin has to accept the role (7 days)
+const ADMIN_PROPOSAL_PERIOD: u64 = 7 * 24 * 60 * 60;
+
+/// Number of most recent receipts kept per subscription; older ones are
+/// deleted once their dispute window has closed
+const RECEIPT_RETENTION: u32 = 24;
+
+/// Most receipts kept per subscription; past this the oldest is deleted even
+/// if it can still be disputed
+const MAX_RECEIPTS: u32 = 48;
+
+/// How long after a payment the subscriber may dispute it (30 days)
+const DISPUTE_WINDOW: u64 = 30 * 24 * 60 * 60;
+
+/// Credit score a subscriber loses when a dispute is resolved against them
+const DISPUTE_CREDIT_PENALTY: u32 = 20;
+
+/// Highest protocol fee that can be charged on a collection (10%)
+const MAX_PROTOCOL_FEE_BPS: u32 = 1_000;
+
+/// Most payees a merchant's revenue can be split between
+const MAX_SPLIT_RECIPIENTS: u32 = 10;
+
+/// Most seats a team subscription can have
+const MAX_SEATS: u32 = 50;
+
+/// Longest settlement delay a merchant can be put on (90 days)
+const MAX_SETTLEMENT_DELAY: u64 = 90 * 24 * 60 * 60;
+
+/// How long a payment may stay past due before the subscription is suspended (7 days)
+const PAST_DUE_GRACE_PERIOD: u64 = 7 * 24 * 60 * 60;
+
+/// Storage schema written by this version of the contract
+/// * v1 - original layouts
+/// * v2 - `UserDebt` tracks itemized BNPL advances
+/// * v3 - active subscriptions are listed in subscriber and merchant indexes
+/// * v4 - `Subscription` carries a `SubscriptionStatus` instead of `is_active`
+/// * v5 - `Subscription` records a `payer` separate from the subscriber
+const SCHEMA_VERSION: u32 = 5;
+
+/// Field counts of the current stored layouts, used to spot older records
+const SUBSCRIPTION_FIELDS: u32 = 10;
+const USER_DEBT_FIELDS: u32 = 4;
+
+/// Field counts of `SubscriptionV4` and `SubscriptionV1`
+const SUBSCRIPTION_V4_FIELDS: u32 = 9;
+const SUBSCRIPTION_V1_FIELDS: u32 = 8;
+
 /// TTL: 30 days in ledgers (assuming ~5 second ledgers)
 const THIRTY_DAYS_LEDGERS: u32 = 30 * 24 * 60 * 60 / 5;
 
+/// Entries touched with less than 15 days left are topped back up to 30 days
+const TTL_THRESHOLD_LEDGERS: u32 = THIRTY_DAYS_LEDGERS / 2;
+
 // ============================================================================
 // CONTRACT
 // ============================================================================
@@ -87,7 +987,7 @@ impl SoroSubContract {
     /// # Arguments
     /// * `env` - The contract environment
     /// * `admin` - The admin address
-    /// * `liquidity_pool` - The liquidity pool address for BNPL funding
+    /// * `liquidity_pool` - The liquidity pool vault contract funding BNPL advances
     pub fn initialize(env: Env, admin: Address, liquidity_pool: Address) {
         // Check if already initialized
         if env.storage().instance().has(&DataKey::Initialized) {
@@ -99,12 +999,13 @@ impl SoroSubContract {
         env.storage()
             .instance()
             .set(&DataKey::LiquidityPool, &liquidity_pool);
+        env.storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &SCHEMA_VERSION);
         env.storage().instance().set(&DataKey::Initialized, &true);
 
         // Extend instance TTL
-        env.storage()
-            .instance()
-            .extend_ttl(THIRTY_DAYS_LEDGERS, THIRTY_DAYS_LEDGERS);
+        Self::extend_instance_ttl(&env);
     }
 
     /// Create a new subscription
@@ -117,6 +1018,11 @@ impl SoroSubContract {
     /// * `amount` - Amount to transfer per payment period
     /// * `interval` - Time in seconds between allowed payments
     ///
+    /// A cancelled subscription to the same merchant is replaced: it is
+    /// folded into the subscriber's credit history, and if its paid period
+    /// hasn't ended yet the new subscription counts from its last payment
+    /// so the period isn't billed twice.
+    ///
     /// # Returns
     /// * The created Subscription struct
     pub fn create_subscription(
@@ -129,74 +1035,396 @@ impl SoroSubContract {
     ) -> Subscription {
         // Subscriber must authorize this action
         subscriber.require_auth();
+        Self::open_subscription(
+            &env,
+            subscriber.clone(),
+            SubscriptionKey {
+                subscriber,
+                merchant,
+            },
+            token,
+            amount,
+            interval,
+            0,
+        )
+    }
 
-        // Validate inputs
-        if amount <= 0 {
-            panic!("Amount must be positive");
-        }
-        if interval == 0 {
-            panic!("Interval must be greater than 0");
-        }
-
-        // Create subscription key
-        let key = SubscriptionKey {
-            subscriber: subscriber.clone(),
-            merchant: merchant.clone(),
-        };
-
-        // Check if subscription already exists
-        let storage_key = DataKey::Subscription(key.clone());
-        if env.storage().persistent().has(&storage_key) {
-            let existing: Subscription = env.storage().persistent().get(&storage_key).unwrap();
-            if existing.is_active {
-                panic!("Active subscription already exists");
-            }
+    /// Create a subscription paid for by someone other than the subscriber
+    ///
+    /// Collections draw on the payer's balance and allowance, and any BNPL
+    /// debt is the payer's. The subscriber is who the subscription is for:
+    /// it is listed and checked under the subscriber's address, and must
+    /// accept the gift by signing too. The payer manages billing (cancel,
+    /// pause and resume).
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `payer` - The address paying for the subscription (must sign)
+    /// * `subscriber` - The address the subscription is for (must sign)
+    /// * `merchant` - The address that will receive payments
+    /// * `token` - The token contract address to use for payments
+    /// * `amount` - Amount to transfer per payment period
+    /// * `interval` - Time in seconds between allowed payments
+    ///
+    /// # Returns
+    /// * The created Subscription struct
+    pub fn create_gift_subscription(
+        env: Env,
+        payer: Address,
+        subscriber: Address,
+        merchant: Address,
+        token: Address,
+        amount: i128,
+        interval: u64,
+    ) -> Subscription {
+        payer.require_auth();
+        subscriber.require_auth();
+        Self::open_subscription(
+            &env,
+            payer,
+            SubscriptionKey {
+                subscriber,
+                merchant,
+            },
+            token,
+            amount,
+            interval,
+            0,
+        )
+    }
+
+    /// Create a new subscription that starts with a free trial
+    ///
+    /// The subscription is `Trialing` until the trial ends; its first
+    /// payment can be collected from then on.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The address authorizing payments (must sign)
+    /// * `merchant` - The address that will receive payments
+    /// * `token` - The token contract address to use for payments
+    /// * `amount` - Amount to transfer per payment period
+    /// * `interval` - Time in seconds between allowed payments
+    /// * `trial_period` - Seconds before the first payment is due
+    ///
+    /// # Returns
+    /// * The created Subscription struct
+    pub fn create_trial_subscription(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        token: Address,
+        amount: i128,
+        interval: u64,
+        trial_period: u64,
+    ) -> Subscription {
+        subscriber.require_auth();
+
+        if trial_period == 0 {
+            panic!("Trial period must be greater than 0");
         }
 
-        // Create the subscription (last_payment_time = 0 allows immediate first payment)
-        let subscription = Subscription {
-            subscriber: subscriber.clone(),
-            merchant: merchant.clone(),
-            token: token.clone(),
+        Self::open_subscription(
+            &env,
+            subscriber.clone(),
+            SubscriptionKey {
+                subscriber,
+                merchant,
+            },
+            token,
             amount,
             interval,
-            last_payment_time: 0,
-            is_active: true,
-            credit_score: 0, // Start with 0 credit score
-        };
+            trial_period,
+        )
+    }
 
-        // Store the subscription
-        env.storage().persistent().set(&storage_key, &subscription);
+    /// Create a team or family subscription billed per seat
+    ///
+    /// The owner pays `price_per_seat` for every seat each period and can
+    /// hand seats to members, who then share the owner's access. The owner
+    /// always has access and doesn't take up a seat.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `owner` - The address paying for the seats (must sign)
+    /// * `merchant` - The address that will receive payments
+    /// * `token` - The token contract address to use for payments
+    /// * `price_per_seat` - Amount per seat per payment period
+    /// * `seats` - Number of seats (at most 50)
+    /// * `interval` - Time in seconds between allowed payments
+    ///
+    /// # Returns
+    /// * The created Subscription struct
+    pub fn create_team_subscription(
+        env: Env,
+        owner: Address,
+        merchant: Address,
+        token: Address,
+        price_per_seat: i128,
+        seats: u32,
+        interval: u64,
+    ) -> Subscription {
+        owner.require_auth();
 
-        // Extend TTL for 30 days
-        env.storage()
-            .persistent()
-            .extend_ttl(&storage_key, THIRTY_DAYS_LEDGERS, THIRTY_DAYS_LEDGERS);
+        if seats == 0 || seats > MAX_SEATS {
+            panic!("Seat count out of range");
+        }
 
-        // Log subscription creation
-        log!(
+        let key = SubscriptionKey {
+            subscriber: owner.clone(),
+            merchant,
+        };
+        let subscription = Self::open_subscription(
             &env,
-            "Subscription Created for {} to {}",
-            subscriber,
-            merchant
+            owner,
+            key.clone(),
+            token,
+            price_per_seat * seats as i128,
+            interval,
+            0,
         );
 
-        // Emit event
-        env.events().publish(
-            (SUB_CREATED, subscriber, merchant),
-            (token, amount, interval),
-        );
+        let plan = SeatPlan {
+            price_per_seat,
+            next_price_per_seat: price_per_seat,
+            seats,
+            next_seats: seats,
+            members: Vec::new(&env),
+        };
+        let plan_key = DataKey::SeatPlan(key);
+        env.storage().persistent().set(&plan_key, &plan);
+        Self::extend_ttl(&env, &plan_key);
 
         subscription
     }
 
+    /// Give a member a seat on a team subscription (owner only)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `owner` - The team subscription's owner (must sign)
+    /// * `merchant` - The merchant's address
+    /// * `member` - The address getting the seat
+    pub fn add_member(env: Env, owner: Address, merchant: Address, member: Address) {
+        owner.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        let key = SubscriptionKey {
+            subscriber: owner.clone(),
+            merchant: merchant.clone(),
+        };
+        let subscription = Self::load_subscription(&env, &DataKey::Subscription(key.clone()))
+            .expect("Subscription not found");
+        if !subscription.status.is_live() {
+            panic_with_error!(&env, SubscriptionError::InvalidTransition);
+        }
+
+        let plan_key = DataKey::SeatPlan(key);
+        let mut plan: SeatPlan = env
+            .storage()
+            .persistent()
+            .get(&plan_key)
+            .expect("Not a team subscription");
+        if plan.members.len() >= plan.seats.min(plan.next_seats) {
+            panic!("No free seats");
+        }
+
+        let membership_key = DataKey::Membership(member.clone(), merchant.clone());
+        if member == owner || env.storage().persistent().has(&membership_key) {
+            panic!("Already a member");
+        }
+
+        plan.members.push_back(member.clone());
+        env.storage().persistent().set(&plan_key, &plan);
+        Self::extend_ttl(&env, &plan_key);
+        env.storage().persistent().set(&membership_key, &owner);
+        Self::extend_ttl(&env, &membership_key);
+
+        MemberAdded {
+            owner,
+            merchant,
+            version: EVENT_VERSION,
+            member,
+        }
+        .publish(&env);
+    }
+
+    /// Take a seat back from a member (owner only)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `owner` - The team subscription's owner (must sign)
+    /// * `merchant` - The merchant's address
+    /// * `member` - The member losing the seat
+    pub fn remove_member(env: Env, owner: Address, merchant: Address, member: Address) {
+        owner.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        let plan_key = DataKey::SeatPlan(SubscriptionKey {
+            subscriber: owner.clone(),
+            merchant: merchant.clone(),
+        });
+        let mut plan: SeatPlan = env
+            .storage()
+            .persistent()
+            .get(&plan_key)
+            .expect("Not a team subscription");
+        let index = plan.members.first_index_of(&member).expect("Not a member");
+
+        plan.members.remove(index);
+        env.storage().persistent().set(&plan_key, &plan);
+        Self::extend_ttl(&env, &plan_key);
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Membership(member.clone(), merchant.clone()));
+
+        MemberRemoved {
+            owner,
+            merchant,
+            version: EVENT_VERSION,
+            member,
+        }
+        .publish(&env);
+    }
+
+    /// Change the number of seats on a team subscription (owner only)
+    ///
+    /// The new seat count is billed from the next successful collection on,
+    /// and stays pending while collections fail. Extra seats can be handed
+    /// out once they have been paid for; seats can only be cut down to the
+    /// current number of members.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `owner` - The team subscription's owner (must sign)
+    /// * `merchant` - The merchant's address
+    /// * `seats` - New number of seats (at most 50)
+    ///
+    /// # Returns
+    /// * The updated SeatPlan
+    pub fn set_seats(env: Env, owner: Address, merchant: Address, seats: u32) -> SeatPlan {
+        owner.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if seats == 0 || seats > MAX_SEATS {
+            panic!("Seat count out of range");
+        }
+
+        let plan_key = DataKey::SeatPlan(SubscriptionKey {
+            subscriber: owner.clone(),
+            merchant: merchant.clone(),
+        });
+        let mut plan: SeatPlan = env
+            .storage()
+            .persistent()
+            .get(&plan_key)
+            .expect("Not a team subscription");
+        if seats < plan.members.len() {
+            panic!("Remove members before reducing seats");
+        }
+
+        plan.next_seats = seats;
+        env.storage().persistent().set(&plan_key, &plan);
+        Self::extend_ttl(&env, &plan_key);
+
+        SeatsScheduled {
+            owner,
+            merchant,
+            version: EVENT_VERSION,
+            seats,
+            next_amount: plan.next_price_per_seat * seats as i128,
+        }
+        .publish(&env);
+
+        plan
+    }
+
+    /// Change the price per seat of a team subscription (merchant only)
+    ///
+    /// Like a seat change, the new price is billed from the next successful
+    /// collection on and stays pending while collections fail.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `owner` - The team subscription's owner
+    /// * `merchant` - The merchant's address (must sign)
+    /// * `price_per_seat` - New amount per seat per payment period
+    ///
+    /// # Returns
+    /// * The updated SeatPlan
+    pub fn set_seat_price(
+        env: Env,
+        owner: Address,
+        merchant: Address,
+        price_per_seat: i128,
+    ) -> SeatPlan {
+        merchant.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if price_per_seat <= 0 {
+            panic!("Amount must be positive");
+        }
+
+        let plan_key = DataKey::SeatPlan(SubscriptionKey {
+            subscriber: owner.clone(),
+            merchant: merchant.clone(),
+        });
+        let mut plan: SeatPlan = env
+            .storage()
+            .persistent()
+            .get(&plan_key)
+            .expect("Not a team subscription");
+
+        plan.next_price_per_seat = price_per_seat;
+        env.storage().persistent().set(&plan_key, &plan);
+        Self::extend_ttl(&env, &plan_key);
+
+        SeatPriceScheduled {
+            owner,
+            merchant,
+            version: EVENT_VERSION,
+            price_per_seat,
+            next_amount: price_per_seat * plan.next_seats as i128,
+        }
+        .publish(&env);
+
+        plan
+    }
+
+    /// Get the seats and members of a team subscription, if it is one
+    pub fn get_seat_plan(env: Env, owner: Address, merchant: Address) -> Option<SeatPlan> {
+        let key = DataKey::SeatPlan(SubscriptionKey {
+            subscriber: owner,
+            merchant,
+        });
+        let plan = env.storage().persistent().get(&key);
+        if plan.is_some() {
+            Self::extend_ttl(&env, &key);
+        }
+        plan
+    }
+
+    /// Get the owner of the team subscription a member has a seat on
+    pub fn get_team_owner(env: Env, member: Address, merchant: Address) -> Option<Address> {
+        let key = DataKey::Membership(member, merchant);
+        let owner = env.storage().persistent().get(&key);
+        if owner.is_some() {
+            Self::extend_ttl(&env, &key);
+        }
+        owner
+    }
+
     /// Collect a subscription payment (pull funds from subscriber)
     ///
     /// This is the main payment collection function that:
-    /// 1. Checks if the payment interval has passed
-    /// 2. Checks subscriber balance
+    /// 1. Checks the subscription can be billed and the payment is due
+    /// 2. Checks the payer's balance (the subscriber unless it was gifted)
     /// 3. If balance is sufficient: normal payment + credit score increment
-    /// 4. If balance is insufficient but credit_score > 50: BNPL trigger
+    /// 4. If balance is insufficient but credit_score > 50: BNPL trigger,
+    ///    with the debt owed by the payer
+    /// 5. After a normal payment, applies the payer's auto-repay setting (if any)
+    /// 6. If neither can fund the payment, marks the subscription `PastDue`,
+    ///    or `Suspended` once it has been past due for the grace period
     ///
     /// # Arguments
     /// * `env` - The contract environment
@@ -204,60 +1432,130 @@ impl SoroSubContract {
     /// * `merchant` - The merchant's address (may authorize to receive payment)
     ///
     /// # Returns
-    /// * The amount transferred
+    /// * The amount transferred (0 if the payment couldn't be funded)
     pub fn collect_payment(env: Env, subscriber: Address, merchant: Address) -> i128 {
+        Self::extend_instance_ttl(&env);
+
+        let pause = Self::load_pause(&env);
+        if pause.collections {
+            panic!("Collections are paused");
+        }
+
         // Get the subscription
         let key = SubscriptionKey {
             subscriber: subscriber.clone(),
             merchant: merchant.clone(),
         };
-        let storage_key = DataKey::Subscription(key);
+        let storage_key = DataKey::Subscription(key.clone());
 
-        let mut subscription: Subscription = env
-            .storage()
-            .persistent()
-            .get(&storage_key)
-            .expect("Subscription not found");
+        let mut subscription =
+            Self::load_subscription(&env, &storage_key).expect("Subscription not found");
+
+        // Paused, suspended and ended subscriptions can't be billed
+        if !subscription.status.is_collectible() {
+            panic_with_error!(&env, SubscriptionError::NotCollectible);
+        }
 
-        // Check if subscription is active
-        if !subscription.is_active {
-            panic!("Subscription is not active");
+        // Merchants are paid nothing more until their disputes are escrowed
+        if Self::load_unescrowed(&env, &merchant) > 0 {
+            panic!("Merchant has unescrowed disputes");
         }
 
         // CRITICAL SECURITY: Check if enough time has passed
         let current_time = env.ledger().timestamp();
-        let is_first_payment = subscription.last_payment_time == 0;
-
-        if !is_first_payment {
-            let next_payment_time = subscription.last_payment_time + subscription.interval;
-            if current_time < next_payment_time {
-                panic!("Payment interval has not passed yet");
-            }
+        let due_time = Self::next_payment_time(&subscription);
+        if current_time < due_time {
+            panic!("Payment interval has not passed yet");
         }
 
-        // Get token client and check subscriber balance
+        // Get token client and check the payer's balance
+        let payer = subscription.payer.clone();
         let token_client = token::Client::new(&env, &subscription.token);
-        let subscriber_balance = token_client.balance(&subscriber);
+        let payer_balance = token_client.balance(&payer);
         let contract_address = env.current_contract_address();
 
-        let payment_amount = subscription.amount;
+        // Seat and price changes take effect with the billing cycle they are
+        // paid in
+        let seat_plan = Self::load_seat_plan(&env, &key);
+        let payment_amount = match &seat_plan {
+            Some(plan) => plan.next_price_per_seat * plan.next_seats as i128,
+            None => subscription.amount,
+        };
+        let config = Self::load_config(&env);
+
+        // Merchants on a settlement delay are paid into the contract and
+        // withdraw once the hold period has passed. Split revenue also passes
+        // through the contract on its way to the payees.
+        let settlement_delay = Self::load_settlement_delay(&env, &merchant);
+        let splits = Self::load_revenue_split(&env, &merchant);
+        let payee = if settlement_delay > 0 || !splits.is_empty() {
+            contract_address.clone()
+        } else {
+            merchant.clone()
+        };
+
+        // The protocol's cut goes to the treasury, the rest to the merchant
+        let treasury: Option<Address> = env.storage().instance().get(&DataKey::Treasury);
+        let protocol_fee = match &treasury {
+            Some(_) => {
+                payment_amount * Self::load_merchant_fee(&env, &merchant) as i128
+                    / BPS_DENOMINATOR as i128
+            }
+            None => 0,
+        };
+        let net_amount = payment_amount - protocol_fee;
 
-        if subscriber_balance >= payment_amount {
+        if payer_balance >= payment_amount {
             // Normal payment flow: sufficient balance
-            // Transfer tokens from subscriber to merchant using allowance
-            token_client.transfer_from(&contract_address, &subscriber, &merchant, &payment_amount);
+            // Transfer tokens from payer to merchant using allowance
+            token_client.transfer_from(&contract_address, &payer, &payee, &net_amount);
+            if let (Some(treasury), true) = (&treasury, protocol_fee > 0) {
+                token_client.transfer_from(&contract_address, &payer, treasury, &protocol_fee);
+            }
 
             // Increment credit score
-            subscription.credit_score += CREDIT_SCORE_INCREMENT;
+            subscription.credit_score += config.credit_score_increment;
+            Self::apply_seat_change(&env, &key, seat_plan, &mut subscription);
 
             // Update last payment time
             subscription.last_payment_time = current_time;
+            if subscription.status != SubscriptionStatus::Active {
+                Self::change_status(&env, &mut subscription, SubscriptionStatus::Active);
+            }
             env.storage().persistent().set(&storage_key, &subscription);
 
             // Extend TTL
-            env.storage()
-                .persistent()
-                .extend_ttl(&storage_key, THIRTY_DAYS_LEDGERS, THIRTY_DAYS_LEDGERS);
+            Self::extend_ttl(&env, &storage_key);
+            let receipt_id = Self::record_receipt(
+                &env,
+                &key,
+                &subscription.token,
+                payment_amount,
+                protocol_fee,
+                FundingSource::Balance,
+            );
+            if settlement_delay > 0 {
+                Self::hold_payment(
+                    &env,
+                    &key,
+                    &subscription.token,
+                    receipt_id,
+                    net_amount,
+                    settlement_delay,
+                );
+            } else if !splits.is_empty() {
+                Self::pay_splits(&env, &splits, &subscription.token, net_amount);
+            }
+            if let (Some(treasury), true) = (treasury, protocol_fee > 0) {
+                Self::record_protocol_fee(
+                    &env,
+                    &merchant,
+                    &subscription.token,
+                    payment_amount,
+                    protocol_fee,
+                    treasury,
+                );
+            }
 
             // Log successful payment
             log!(
@@ -269,45 +1567,131 @@ impl SoroSubContract {
             );
 
             // Emit payment event
-            env.events().publish(
-                (PAYMENT, subscriber.clone(), merchant.clone()),
-                (subscription.token.clone(), payment_amount, subscription.credit_score),
+            PaymentCollected {
+                subscriber: subscriber.clone(),
+                merchant: merchant.clone(),
+                version: EVENT_VERSION,
+                token: subscription.token.clone(),
+                amount: payment_amount,
+                credit_score: subscription.credit_score,
+            }
+            .publish(&env);
+
+            // Opt-in auto-repay: put part of any surplus toward outstanding debt
+            Self::auto_repay_debt(
+                &env,
+                &payer,
+                &subscription.token,
+                payer_balance - payment_amount,
             );
-        } else if subscription.credit_score > BNPL_MIN_CREDIT_SCORE {
+        } else if subscription.credit_score > config.bnpl_min_credit_score {
             // BNPL: User has good credit but insufficient balance
-            // Transfer from liquidity pool to merchant instead
+            if pause.bnpl {
+                panic!("BNPL advances are paused");
+            }
+            if Self::is_defaulted(&env, &payer) {
+                panic!("BNPL unavailable after default");
+            }
+
+            // The liquidity pool vault pays the merchant instead
             let liquidity_pool: Address = env
                 .storage()
                 .instance()
                 .get(&DataKey::LiquidityPool)
                 .expect("Liquidity pool not initialized");
 
-            // Transfer from liquidity pool to merchant
-            token_client.transfer_from(&contract_address, &liquidity_pool, &merchant, &payment_amount);
+            // Refuse advances the vault can't safely cover
+            let pool_client = LiquidityPoolClient::new(&env, &liquidity_pool);
+            Self::check_pool_capacity(
+                &env,
+                &config,
+                &pool_client,
+                &subscription.token,
+                payment_amount,
+            );
+
+            // Draw the advance from vault reserves straight to the merchant
+            pool_client.draw(&subscription.token, &payee, &net_amount);
+            if let (Some(treasury), true) = (&treasury, protocol_fee > 0) {
+                pool_client.draw(&subscription.token, treasury, &protocol_fee);
+            }
 
-            // Record debt against user
-            let debt_key = DataKey::UserDebt(subscriber.clone());
-            let mut user_debt: UserDebt = env
-                .storage()
-                .persistent()
-                .get(&debt_key)
-                .unwrap_or(UserDebt {
-                    amount: 0,
-                    token: subscription.token.clone(),
-                });
+            // Record debt against the payer
+            let debt_key = DataKey::UserDebt(payer.clone());
+            let mut user_debt = Self::load_user_debt(&env, &payer).unwrap_or(UserDebt {
+                amount: 0,
+                token: subscription.token.clone(),
+                advance_count: 0,
+                next_unpaid: 0,
+            });
 
-            user_debt.amount += payment_amount;
+            if user_debt.amount > 0 && user_debt.token != subscription.token {
+                panic!("Outstanding debt is in a different token");
+            }
+
+            // Itemize the advance so it can be audited and repaid oldest-first
+            let fee = payment_amount * config.bnpl_fee_bps as i128 / BPS_DENOMINATOR as i128;
+            let advance = BnplAdvance {
+                id: user_debt.advance_count,
+                merchant: merchant.clone(),
+                token: subscription.token.clone(),
+                amount: payment_amount,
+                fee,
+                timestamp: current_time,
+                repaid: 0,
+                written_off: false,
+                pool: liquidity_pool.clone(),
+            };
+            let advance_key = DataKey::Advance(payer.clone(), advance.id);
+            env.storage().persistent().set(&advance_key, &advance);
+            Self::extend_ttl(&env, &advance_key);
+
+            user_debt.amount += payment_amount + fee;
             user_debt.token = subscription.token.clone();
+            user_debt.advance_count += 1;
             env.storage().persistent().set(&debt_key, &user_debt);
+            Self::adjust_total_debt(&env, &liquidity_pool, &subscription.token, payment_amount);
 
             // Extend debt TTL
-            env.storage()
-                .persistent()
-                .extend_ttl(&debt_key, THIRTY_DAYS_LEDGERS, THIRTY_DAYS_LEDGERS);
+            Self::extend_ttl(&env, &debt_key);
 
             // Update last payment time (BNPL still counts as a payment)
             subscription.last_payment_time = current_time;
+            Self::apply_seat_change(&env, &key, seat_plan, &mut subscription);
+            if subscription.status != SubscriptionStatus::Active {
+                Self::change_status(&env, &mut subscription, SubscriptionStatus::Active);
+            }
             env.storage().persistent().set(&storage_key, &subscription);
+            let receipt_id = Self::record_receipt(
+                &env,
+                &key,
+                &subscription.token,
+                payment_amount,
+                protocol_fee,
+                FundingSource::Bnpl(advance.id),
+            );
+            if settlement_delay > 0 {
+                Self::hold_payment(
+                    &env,
+                    &key,
+                    &subscription.token,
+                    receipt_id,
+                    net_amount,
+                    settlement_delay,
+                );
+            } else if !splits.is_empty() {
+                Self::pay_splits(&env, &splits, &subscription.token, net_amount);
+            }
+            if let (Some(treasury), true) = (treasury, protocol_fee > 0) {
+                Self::record_protocol_fee(
+                    &env,
+                    &merchant,
+                    &subscription.token,
+                    payment_amount,
+                    protocol_fee,
+                    treasury,
+                );
+            }
 
             // Log BNPL trigger
             log!(
@@ -319,193 +1703,738 @@ impl SoroSubContract {
             );
 
             // Emit BNPL event
-            env.events().publish(
-                (BNPL_TRIGGER, subscriber.clone(), merchant.clone()),
-                (subscription.token.clone(), payment_amount, user_debt.amount),
-            );
+            BnplAdvanced {
+                subscriber: subscriber.clone(),
+                merchant: merchant.clone(),
+                version: EVENT_VERSION,
+                token: subscription.token.clone(),
+                amount: payment_amount,
+                fee,
+                advance_id: advance.id,
+                total_debt: user_debt.amount,
+            }
+            .publish(&env);
         } else {
-            // Insufficient balance AND insufficient credit score
-            panic!("Insufficient balance and credit score too low for BNPL");
+            // Insufficient balance AND insufficient credit score: the payment
+            // stays due and is retried by the next collection
+            let status = if subscription.status == SubscriptionStatus::PastDue
+                && current_time >= due_time + PAST_DUE_GRACE_PERIOD
+            {
+                SubscriptionStatus::Suspended
+            } else {
+                SubscriptionStatus::PastDue
+            };
+            if subscription.status != status {
+                Self::change_status(&env, &mut subscription, status);
+            }
+            env.storage().persistent().set(&storage_key, &subscription);
+
+            log!(
+                &env,
+                "Payment failed: {} has insufficient balance and credit for BNPL",
+                subscriber
+            );
+
+            return 0;
         }
 
         payment_amount
     }
 
-    /// Cancel a subscription
+    /// Cancel a subscription (any live status)
     ///
     /// # Arguments
     /// * `env` - The contract environment
-    /// * `subscriber` - The subscriber's address (must sign)
+    /// * `subscriber` - The subscriber's address
     /// * `merchant` - The merchant's address
+    ///
+    /// The payer must sign; that is the subscriber unless it was gifted.
     pub fn cancel_subscription(env: Env, subscriber: Address, merchant: Address) {
-        // Subscriber must authorize cancellation
-        subscriber.require_auth();
+        Self::extend_instance_ttl(&env);
 
         // Get the subscription
         let key = SubscriptionKey {
             subscriber: subscriber.clone(),
             merchant: merchant.clone(),
         };
-        let storage_key = DataKey::Subscription(key);
+        let storage_key = DataKey::Subscription(key.clone());
 
-        let mut subscription: Subscription = env
-            .storage()
-            .persistent()
-            .get(&storage_key)
-            .expect("Subscription not found");
+        let mut subscription =
+            Self::load_subscription(&env, &storage_key).expect("Subscription not found");
+
+        // Payer must authorize cancellation
+        subscription.payer.require_auth();
 
-        // Deactivate the subscription
-        subscription.is_active = false;
+        // End the subscription
+        Self::change_status(&env, &mut subscription, SubscriptionStatus::Cancelled);
         env.storage().persistent().set(&storage_key, &subscription);
+        Self::extend_ttl(&env, &storage_key);
+
+        // Only live subscriptions are listed
+        Self::unindex_subscription(&env, &key);
 
         // Emit event
-        env.events()
-            .publish((SUB_CANCELLED, subscriber, merchant), ());
+        SubscriptionCancelled {
+            subscriber,
+            merchant,
+            version: EVENT_VERSION,
+            token: subscription.token,
+            last_payment_time: subscription.last_payment_time,
+            credit_score: subscription.credit_score,
+        }
+        .publish(&env);
     }
 
-    /// Get subscription details
+    /// Pause a trialing or active subscription; nothing can be collected
+    /// until it is resumed
     ///
     /// # Arguments
     /// * `env` - The contract environment
     /// * `subscriber` - The subscriber's address
     /// * `merchant` - The merchant's address
     ///
-    /// # Returns
-    /// * The Subscription struct if it exists
-    pub fn get_subscription(env: Env, subscriber: Address, merchant: Address) -> Subscription {
-        let key = SubscriptionKey {
+    /// The payer must sign; that is the subscriber unless it was gifted.
+    pub fn pause_subscription(env: Env, subscriber: Address, merchant: Address) {
+        Self::extend_instance_ttl(&env);
+
+        let storage_key = DataKey::Subscription(SubscriptionKey {
             subscriber,
             merchant,
-        };
-        let storage_key = DataKey::Subscription(key);
+        });
+        let mut subscription =
+            Self::load_subscription(&env, &storage_key).expect("Subscription not found");
+        subscription.payer.require_auth();
 
-        env.storage()
-            .persistent()
-            .get(&storage_key)
-            .expect("Subscription not found")
+        Self::change_status(&env, &mut subscription, SubscriptionStatus::Paused);
+        env.storage().persistent().set(&storage_key, &subscription);
+        Self::extend_ttl(&env, &storage_key);
     }
 
-    /// Check if a payment can be processed now
+    /// Resume a paused or suspended subscription. Any payment that fell due
+    /// in the meantime can be collected straight away.
     ///
     /// # Arguments
     /// * `env` - The contract environment
     /// * `subscriber` - The subscriber's address
     /// * `merchant` - The merchant's address
     ///
-    /// # Returns
-    /// * true if payment can be processed, false otherwise
-    pub fn can_process_payment(env: Env, subscriber: Address, merchant: Address) -> bool {
-        let key = SubscriptionKey {
+    /// The payer must sign; that is the subscriber unless it was gifted.
+    pub fn resume_subscription(env: Env, subscriber: Address, merchant: Address) {
+        Self::extend_instance_ttl(&env);
+
+        let storage_key = DataKey::Subscription(SubscriptionKey {
             subscriber,
             merchant,
-        };
-        let storage_key = DataKey::Subscription(key);
+        });
+        let mut subscription =
+            Self::load_subscription(&env, &storage_key).expect("Subscription not found");
+        subscription.payer.require_auth();
 
-        if let Some(subscription) = env.storage().persistent().get::<_, Subscription>(&storage_key)
-        {
-            if !subscription.is_active {
-                return false;
-            }
+        // Past-due subscriptions become active again by paying, not resuming
+        if !matches!(
+            subscription.status,
+            SubscriptionStatus::Paused | SubscriptionStatus::Suspended
+        ) {
+            panic_with_error!(&env, SubscriptionError::InvalidTransition);
+        }
+        Self::change_status(&env, &mut subscription, SubscriptionStatus::Active);
+        env.storage().persistent().set(&storage_key, &subscription);
+        Self::extend_ttl(&env, &storage_key);
+    }
 
-            // First payment is always allowed (last_payment_time == 0)
-            if subscription.last_payment_time == 0 {
-                return true;
-            }
+    /// End a subscription from the merchant's side (any live status)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address (must sign)
+    pub fn complete_subscription(env: Env, subscriber: Address, merchant: Address) {
+        merchant.require_auth();
+        Self::update_status(
+            &env,
+            subscriber.clone(),
+            merchant.clone(),
+            SubscriptionStatus::Completed,
+        );
 
-            let current_time = env.ledger().timestamp();
-            let next_payment_time = subscription.last_payment_time + subscription.interval;
-            current_time >= next_payment_time
-        } else {
-            false
-        }
+        // Only live subscriptions are listed
+        Self::unindex_subscription(
+            &env,
+            &SubscriptionKey {
+                subscriber,
+                merchant,
+            },
+        );
     }
 
-    /// Get user's outstanding BNPL debt
+    /// Refund (part of) a collected payment (merchant only)
+    ///
+    /// The refund comes out of the payment's unsettled funds while they are
+    /// still held, and from the merchant otherwise. If the payment
+    /// was fronted by the liquidity pool, the refund repays the payer's
+    /// outstanding BNPL debt first and only the rest reaches the payer (the
+    /// subscriber unless the subscription was gifted).
+    /// Refunds add up per receipt and can't exceed the amount the merchant
+    /// received: the protocol fee stays with the treasury.
     ///
     /// # Arguments
     /// * `env` - The contract environment
-    /// * `user` - The user's address
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address (must sign)
+    /// * `receipt_id` - Receipt of the payment being refunded
+    /// * `amount` - Amount to refund
     ///
     /// # Returns
-    /// * Some(UserDebt) if debt exists, None otherwise
-    pub fn get_user_debt(env: Env, user: Address) -> Option<UserDebt> {
-        let debt_key = DataKey::UserDebt(user);
-        env.storage()
+    /// * The updated Receipt
+    pub fn refund(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        receipt_id: u32,
+        amount: i128,
+    ) -> Receipt {
+        merchant.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if amount <= 0 {
+            panic!("Refund amount must be positive");
+        }
+
+        let receipt_key = DataKey::Receipt(
+            SubscriptionKey {
+                subscriber: subscriber.clone(),
+                merchant: merchant.clone(),
+            },
+            receipt_id,
+        );
+        let mut receipt: Receipt = env
+            .storage()
             .persistent()
-            .get(&debt_key)
+            .get(&receipt_key)
+            .expect("Receipt not found");
+        if amount > receipt.refundable() {
+            panic!("Refund exceeds receipt amount");
+        }
+
+        // Funds still held for the merchant are refunded first
+        let key = SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        };
+        let payer = Self::load_payer(&env, &key);
+        let from_hold = Self::take_from_hold(&env, &key, &receipt, amount);
+        let mut to_debt = 0;
+        if from_hold > 0 {
+            to_debt += Self::pay_refund(
+                &env,
+                &env.current_contract_address(),
+                &payer,
+                &receipt.token,
+                &receipt.source,
+                from_hold,
+            );
+        }
+        if amount > from_hold {
+            to_debt += Self::pay_refund(
+                &env,
+                &merchant,
+                &payer,
+                &receipt.token,
+                &receipt.source,
+                amount - from_hold,
+            );
+        }
+
+        receipt.refunded += amount;
+        env.storage().persistent().set(&receipt_key, &receipt);
+        Self::extend_ttl(&env, &receipt_key);
+
+        log!(
+            &env,
+            "Refund: {} tokens from {} to {} ({} toward debt)",
+            amount,
+            merchant,
+            subscriber,
+            to_debt
+        );
+
+        Refunded {
+            subscriber,
+            merchant,
+            version: EVENT_VERSION,
+            receipt_id,
+            token: receipt.token.clone(),
+            amount,
+            to_debt,
+        }
+        .publish(&env);
+
+        receipt
     }
 
-    /// Repay BNPL debt
+    /// Dispute (part of) a collected payment (subscriber only)
+    ///
+    /// Payments can be disputed within 30 days of collection, once per
+    /// receipt and up to the amount the merchant received (the protocol fee
+    /// is not refundable) and hasn't refunded yet. If the payment is still
+    /// unsettled, the disputed funds move straight into escrow; otherwise
+    /// the merchant should escrow them with `escrow_dispute`. An arbiter then
+    /// decides with `resolve_dispute`. The subscriber disputes gift
+    /// subscriptions too; a refund still goes to the payer.
     ///
     /// # Arguments
     /// * `env` - The contract environment
-    /// * `user` - The user repaying debt (must sign)
-    /// * `amount` - Amount to repay
+    /// * `subscriber` - The subscriber's address (must sign)
+    /// * `merchant` - The merchant's address
+    /// * `receipt_id` - Receipt of the disputed payment
+    /// * `amount` - Amount in dispute
     ///
     /// # Returns
-    /// * Remaining debt amount
-    pub fn repay_debt(env: Env, user: Address, amount: i128) -> i128 {
-        // User must authorize repayment
-        user.require_auth();
+    /// * The new Dispute
+    pub fn open_dispute(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        receipt_id: u32,
+        amount: i128,
+    ) -> Dispute {
+        subscriber.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        let key = SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        };
 
         if amount <= 0 {
-            panic!("Repayment amount must be positive");
+            panic!("Dispute amount must be positive");
+        }
+        let receipt: Receipt = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Receipt(key.clone(), receipt_id))
+            .expect("Receipt not found");
+        if env.ledger().timestamp() > receipt.timestamp + DISPUTE_WINDOW {
+            panic!("Dispute window has closed");
+        }
+        if amount > receipt.refundable() {
+            panic!("Dispute exceeds refundable amount");
         }
 
-        let debt_key = DataKey::UserDebt(user.clone());
-        let mut user_debt: UserDebt = env
+        let dispute_key = DataKey::Dispute(key.clone(), receipt_id);
+        if env.storage().persistent().has(&dispute_key) {
+            panic!("Payment already disputed");
+        }
+
+        // Funds still held for the merchant go straight into escrow
+        let escrowed = Self::take_from_hold(&env, &key, &receipt, amount);
+
+        let dispute = Dispute {
+            receipt_id,
+            token: receipt.token,
+            source: receipt.source,
+            amount,
+            escrowed,
+            held: escrowed,
+            opened_at: env.ledger().timestamp(),
+            status: DisputeStatus::Open,
+        };
+        env.storage().persistent().set(&dispute_key, &dispute);
+        Self::extend_ttl(&env, &dispute_key);
+        Self::save_open_disputes(&env, &key, Self::load_open_disputes(&env, &key) + 1);
+        if escrowed < amount {
+            Self::save_unescrowed(&env, &merchant, Self::load_unescrowed(&env, &merchant) + 1);
+        }
+
+        DisputeOpened {
+            subscriber,
+            merchant,
+            version: EVENT_VERSION,
+            receipt_id,
+            amount,
+        }
+        .publish(&env);
+
+        dispute
+    }
+
+    /// Put the funds of an open dispute in escrow (merchant only)
+    ///
+    /// Moves whatever is still missing from the escrow into the contract,
+    /// where it stays until the dispute is resolved.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address (must sign)
+    /// * `receipt_id` - Receipt of the disputed payment
+    ///
+    /// # Returns
+    /// * The updated Dispute
+    pub fn escrow_dispute(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        receipt_id: u32,
+    ) -> Dispute {
+        merchant.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        let dispute_key = DataKey::Dispute(
+            SubscriptionKey {
+                subscriber: subscriber.clone(),
+                merchant: merchant.clone(),
+            },
+            receipt_id,
+        );
+        let mut dispute: Dispute = env
             .storage()
             .persistent()
-            .get(&debt_key)
-            .expect("No debt found for user");
+            .get(&dispute_key)
+            .expect("Dispute not found");
+        if dispute.status != DisputeStatus::Open {
+            panic!("Dispute already resolved");
+        }
 
-        if amount > user_debt.amount {
-            panic!("Repayment amount exceeds debt");
+        let missing = dispute.amount - dispute.escrowed;
+        if missing > 0 {
+            token::Client::new(&env, &dispute.token).transfer(
+                &merchant,
+                env.current_contract_address(),
+                &missing,
+            );
+            dispute.escrowed += missing;
+            env.storage().persistent().set(&dispute_key, &dispute);
+            Self::save_unescrowed(&env, &merchant, Self::load_unescrowed(&env, &merchant) - 1);
         }
+        Self::extend_ttl(&env, &dispute_key);
 
-        // Get liquidity pool to receive repayment
-        let liquidity_pool: Address = env
+        DisputeEscrowed {
+            subscriber,
+            merchant,
+            version: EVENT_VERSION,
+            receipt_id,
+            escrowed: dispute.escrowed,
+        }
+        .publish(&env);
+
+        dispute
+    }
+
+    /// Resolve an open dispute (arbiter only)
+    ///
+    /// A merchant's dedicated arbiter, if one is set, is the only one who can
+    /// resolve its disputes; otherwise any holder of the arbiter role can.
+    /// If the subscriber wins, the escrowed funds are refunded like a
+    /// merchant refund, BNPL debt first. If the merchant wins, the escrow is
+    /// released back to the merchant and the subscriber's credit score on
+    /// the subscription drops. Escrow that came from the merchant's unsettled
+    /// funds is paid out like a settlement, through any revenue split.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `arbiter` - The deciding arbiter (must sign)
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address
+    /// * `receipt_id` - Receipt of the disputed payment
+    /// * `subscriber_wins` - Whether the dispute is upheld
+    ///
+    /// # Returns
+    /// * The resolved Dispute
+    pub fn resolve_dispute(
+        env: Env,
+        arbiter: Address,
+        subscriber: Address,
+        merchant: Address,
+        receipt_id: u32,
+        subscriber_wins: bool,
+    ) -> Dispute {
+        match Self::get_merchant_arbiter(env.clone(), merchant.clone()) {
+            Some(merchant_arbiter) => {
+                Self::extend_instance_ttl(&env);
+                arbiter.require_auth();
+                if arbiter != merchant_arbiter {
+                    panic!("Caller is not the merchant's arbiter");
+                }
+            }
+            None => Self::require_role(&env, &arbiter, Role::Arbiter),
+        }
+
+        let key = SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        };
+        let dispute_key = DataKey::Dispute(key.clone(), receipt_id);
+        let mut dispute: Dispute = env
             .storage()
-            .instance()
-            .get(&DataKey::LiquidityPool)
-            .expect("Liquidity pool not initialized");
+            .persistent()
+            .get(&dispute_key)
+            .expect("Dispute not found");
+        if dispute.status != DisputeStatus::Open {
+            panic!("Dispute already resolved");
+        }
+        if dispute.escrowed < dispute.amount {
+            panic!("Dispute is not escrowed");
+        }
 
-        // Transfer repayment from user to liquidity pool
-        let token_client = token::Client::new(&env, &user_debt.token);
-        token_client.transfer(&user, &liquidity_pool, &amount);
+        Self::save_open_disputes(&env, &key, Self::load_open_disputes(&env, &key) - 1);
 
-        // Update debt
-        user_debt.amount -= amount;
+        let contract = env.current_contract_address();
+        let receipt_key = DataKey::Receipt(key.clone(), receipt_id);
+        let receipt: Option<Receipt> = env.storage().persistent().get(&receipt_key);
+        let receipt_missing = receipt.is_none();
+        let mut record = Self::get_merchant_disputes(env.clone(), merchant.clone());
+        let mut refunded = 0;
+        if subscriber_wins {
+            // The merchant may have refunded part of it since the dispute opened
+            refunded = match &receipt {
+                Some(receipt) => dispute.escrowed.min(receipt.refundable()),
+                None => dispute.escrowed,
+            };
+            if refunded > 0 {
+                Self::pay_refund(
+                    &env,
+                    &contract,
+                    &Self::load_payer(&env, &key),
+                    &dispute.token,
+                    &dispute.source,
+                    refunded,
+                );
+            }
+            if let Some(mut receipt) = receipt {
+                receipt.refunded += refunded;
+                env.storage().persistent().set(&receipt_key, &receipt);
+                Self::extend_ttl(&env, &receipt_key);
+            }
+            dispute.status = DisputeStatus::ResolvedForSubscriber;
+            record.lost += 1;
+        } else {
+            let storage_key = DataKey::Subscription(key);
+            if let Some(mut subscription) = Self::load_subscription(&env, &storage_key) {
+                subscription.credit_score = subscription
+                    .credit_score
+                    .saturating_sub(DISPUTE_CREDIT_PENALTY);
+                env.storage().persistent().set(&storage_key, &subscription);
+                Self::extend_ttl(&env, &storage_key);
+            }
+            dispute.status = DisputeStatus::ResolvedForMerchant;
+            record.won += 1;
+        }
 
-        if user_debt.amount == 0 {
-            // Remove debt record if fully repaid
-            env.storage().persistent().remove(&debt_key);
+        // Whatever wasn't refunded goes back to the merchant. Held funds
+        // never reached the payees, so they are paid out like a settlement.
+        let returned = dispute.escrowed - refunded;
+        let from_hold = returned.min(dispute.held);
+        if from_hold > 0 {
+            Self::pay_merchant(&env, &merchant, &dispute.token, from_hold);
+        }
+        if returned > from_hold {
+            token::Client::new(&env, &dispute.token).transfer(
+                &contract,
+                &merchant,
+                &(returned - from_hold),
+            );
+        }
+        if receipt_missing {
+            // Its receipt was deleted past the receipt cap; nothing would
+            // delete the dispute later
+            env.storage().persistent().remove(&dispute_key);
         } else {
-            env.storage().persistent().set(&debt_key, &user_debt);
-            env.storage()
+            env.storage().persistent().set(&dispute_key, &dispute);
+            Self::extend_ttl(&env, &dispute_key);
+        }
+
+        let record_key = DataKey::MerchantDisputes(merchant.clone());
+        env.storage().persistent().set(&record_key, &record);
+        Self::extend_ttl(&env, &record_key);
+
+        DisputeResolved {
+            subscriber,
+            merchant,
+            version: EVENT_VERSION,
+            receipt_id,
+            arbiter,
+            status: dispute.status,
+            refunded,
+        }
+        .publish(&env);
+
+        dispute
+    }
+
+    /// Get the dispute against a payment, if any
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address
+    /// * `receipt_id` - Receipt of the disputed payment
+    pub fn get_dispute(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        receipt_id: u32,
+    ) -> Option<Dispute> {
+        let key = DataKey::Dispute(
+            SubscriptionKey {
+                subscriber,
+                merchant,
+            },
+            receipt_id,
+        );
+        let dispute = env.storage().persistent().get(&key);
+        if dispute.is_some() {
+            Self::extend_ttl(&env, &key);
+        }
+        dispute
+    }
+
+    /// Get how many disputes a merchant has won and lost
+    pub fn get_merchant_disputes(env: Env, merchant: Address) -> MerchantDisputes {
+        let key = DataKey::MerchantDisputes(merchant);
+        let record: Option<MerchantDisputes> = env.storage().persistent().get(&key);
+        if record.is_some() {
+            Self::extend_ttl(&env, &key);
+        }
+        record.unwrap_or_default()
+    }
+
+    /// Withdraw a merchant's held funds whose settlement delay has passed
+    /// (merchant only)
+    ///
+    /// Holds are released oldest first, at most 50 per call, stopping at
+    /// the first one still in its hold period. The payout follows the
+    /// merchant's revenue split, if it has one.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `merchant` - The merchant's address (must sign)
+    /// * `token` - Token to withdraw
+    ///
+    /// # Returns
+    /// * The amount paid out to the merchant
+    pub fn withdraw_settlement(env: Env, merchant: Address, token: Address) -> i128 {
+        merchant.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if Self::load_unescrowed(&env, &merchant) > 0 {
+            panic!("Merchant has unescrowed disputes");
+        }
+
+        let mut settlement = Self::load_settlement(&env, &merchant, &token);
+        let now = env.ledger().timestamp();
+        let mut amount = 0;
+        let mut holds = 0;
+        while holds < MAX_PAGE_SIZE && settlement.next_release < settlement.next_hold {
+            let hold_key = DataKey::Hold(merchant.clone(), token.clone(), settlement.next_release);
+            let hold: Hold = env
+                .storage()
                 .persistent()
-                .extend_ttl(&debt_key, THIRTY_DAYS_LEDGERS, THIRTY_DAYS_LEDGERS);
+                .get(&hold_key)
+                .expect("Hold not found");
+            if hold.release_at > now {
+                break;
+            }
+
+            amount += hold.amount;
+            env.storage().persistent().remove(&hold_key);
+            env.storage().persistent().remove(&DataKey::HoldOf(
+                SubscriptionKey {
+                    subscriber: hold.subscriber,
+                    merchant: merchant.clone(),
+                },
+                hold.receipt_id,
+                hold.collected_at,
+            ));
+            settlement.next_release += 1;
+            holds += 1;
         }
 
-        // Log repayment
-        log!(
-            &env,
-            "Debt repaid: {} tokens by {}, remaining: {}",
+        if holds == 0 {
+            return 0;
+        }
+        settlement.unsettled -= amount;
+        Self::save_settlement(&env, &merchant, &token, &settlement);
+        if amount > 0 {
+            Self::pay_merchant(&env, &merchant, &token, amount);
+        }
+
+        Settled {
+            merchant,
+            token,
+            version: EVENT_VERSION,
             amount,
-            user,
-            user_debt.amount
-        );
+            holds,
+        }
+        .publish(&env);
 
-        // Emit repayment event
-        env.events().publish(
-            (DEBT_REPAID, user.clone()),
-            (amount, user_debt.amount),
-        );
+        amount
+    }
 
-        user_debt.amount
+    /// Get a merchant's unsettled funds in a token
+    pub fn get_settlement(env: Env, merchant: Address, token: Address) -> Settlement {
+        Self::load_settlement(&env, &merchant, &token)
     }
 
-    /// Get the user's credit score from their subscription
+    /// Split a merchant's revenue between payees (merchant only)
+    ///
+    /// Each collection's net amount is divided by the shares, which must add
+    /// up to 100%. Any rounding remainder goes to the first recipient. An
+    /// empty table pays the merchant directly again.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `merchant` - The merchant's address (must sign)
+    /// * `splits` - Recipients and their shares in basis points (at most 10)
+    pub fn set_revenue_split(env: Env, merchant: Address, splits: Vec<Split>) {
+        merchant.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        let key = DataKey::RevenueSplit(merchant.clone());
+        if splits.is_empty() {
+            env.storage().persistent().remove(&key);
+        } else {
+            if splits.len() > MAX_SPLIT_RECIPIENTS {
+                panic!("Too many split recipients");
+            }
+            let mut total: u32 = 0;
+            for split in splits.iter() {
+                if split.share_bps == 0 {
+                    panic!("Split shares must be positive");
+                }
+                total = total.saturating_add(split.share_bps);
+            }
+            if total != BPS_DENOMINATOR {
+                panic!("Split shares must sum to 100%");
+            }
+            env.storage().persistent().set(&key, &splits);
+            Self::extend_ttl(&env, &key);
+        }
+
+        RevenueSplitUpdated {
+            merchant,
+            version: EVENT_VERSION,
+            splits,
+        }
+        .publish(&env);
+    }
+
+    /// Get a merchant's revenue split (empty if paid directly)
+    pub fn get_revenue_split(env: Env, merchant: Address) -> Vec<Split> {
+        Self::load_revenue_split(&env, &merchant)
+    }
+
+    /// Get a held payment by merchant, token and hold id, if not yet withdrawn
+    pub fn get_hold(env: Env, merchant: Address, token: Address, id: u32) -> Option<Hold> {
+        let key = DataKey::Hold(merchant, token, id);
+        let hold = env.storage().persistent().get(&key);
+        if hold.is_some() {
+            Self::extend_ttl(&env, &key);
+        }
+        hold
+    }
+
+    /// Get subscription details
     ///
     /// # Arguments
     /// * `env` - The contract environment
@@ -513,34 +2442,2480 @@ impl SoroSubContract {
     /// * `merchant` - The merchant's address
     ///
     /// # Returns
-    /// * The credit score (u32)
-    pub fn get_credit_score(env: Env, subscriber: Address, merchant: Address) -> u32 {
+    /// * The Subscription struct if it exists
+    pub fn get_subscription(env: Env, subscriber: Address, merchant: Address) -> Subscription {
         let key = SubscriptionKey {
             subscriber,
             merchant,
         };
         let storage_key = DataKey::Subscription(key);
 
-        let subscription: Subscription = env
+        Self::load_subscription(&env, &storage_key).expect("Subscription not found")
+    }
+
+    /// Check whether an address is a paid-up subscriber of a merchant
+    ///
+    /// Meant to be called by other contracts gating their features on a
+    /// subscription. Access comes from the address's own subscription, or
+    /// from a seat on a team subscription:
+    /// * Trialing subscriptions give access until the trial ends
+    /// * Active and past due subscriptions give access once a payment has
+    ///   been collected, until the paid period ends plus the 7-day grace
+    ///   period
+    /// * Cancelled and completed subscriptions give access until the end
+    ///   of the period already paid for
+    /// * Paused and suspended subscriptions give no access
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The address to check
+    /// * `merchant` - The merchant's address
+    ///
+    /// # Returns
+    /// * true if the address currently has access
+    pub fn has_access(env: Env, subscriber: Address, merchant: Address) -> bool {
+        let grants_access = |owner: Address| {
+            let storage_key = DataKey::Subscription(SubscriptionKey {
+                subscriber: owner,
+                merchant: merchant.clone(),
+            });
+            Self::load_subscription(&env, &storage_key)
+                .is_some_and(|subscription| Self::is_paid_up(&env, &subscription))
+        };
+
+        grants_access(subscriber.clone())
+            || Self::get_team_owner(env.clone(), subscriber, merchant.clone())
+                .is_some_and(grants_access)
+    }
+
+    /// Check if a payment can be processed now
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address
+    ///
+    /// # Returns
+    /// * true if payment can be processed, false otherwise
+    pub fn can_process_payment(env: Env, subscriber: Address, merchant: Address) -> bool {
+        let key = SubscriptionKey {
+            subscriber,
+            merchant,
+        };
+        let storage_key = DataKey::Subscription(key);
+
+        if let Some(subscription) = Self::load_subscription(&env, &storage_key) {
+            if !subscription.status.is_collectible() {
+                return false;
+            }
+
+            env.ledger().timestamp() >= Self::next_payment_time(&subscription)
+        } else {
+            false
+        }
+    }
+
+    /// Get user's outstanding BNPL debt
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `user` - The user's address
+    ///
+    /// # Returns
+    /// * Some(UserDebt) if debt is outstanding, None otherwise
+    pub fn get_user_debt(env: Env, user: Address) -> Option<UserDebt> {
+        Self::load_user_debt(&env, &user).filter(|debt| debt.amount > 0)
+    }
+
+    /// Get a single BNPL advance by id
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `user` - The user the advance was made for
+    /// * `id` - The advance id
+    ///
+    /// # Returns
+    /// * The BnplAdvance record
+    pub fn get_advance(env: Env, user: Address, id: u32) -> BnplAdvance {
+        let advance_key = DataKey::Advance(user, id);
+        let advance = env
+            .storage()
+            .persistent()
+            .get(&advance_key)
+            .expect("Advance not found");
+        Self::extend_ttl(&env, &advance_key);
+        advance
+    }
+
+    /// Extend the TTL of a subscription, its index entries and its retained
+    /// receipts (anyone)
+    ///
+    /// Collections keep a subscription alive on their own; this lets a
+    /// keeper or the subscriber keep a rarely-billed subscription from
+    /// being archived.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address
+    pub fn bump_subscription(env: Env, subscriber: Address, merchant: Address) {
+        Self::extend_instance_ttl(&env);
+
+        let key = SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        };
+        Self::load_subscription(&env, &DataKey::Subscription(key.clone()))
+            .expect("Subscription not found");
+
+        let receipt_count = Self::receipt_count(&env, &key);
+        if receipt_count > 0 {
+            Self::extend_ttl(&env, &DataKey::ReceiptCount(key.clone()));
+            for id in Self::first_receipt(&env, &key)..receipt_count {
+                Self::extend_ttl(&env, &DataKey::Receipt(key.clone(), id));
+            }
+            let start_key = DataKey::ReceiptStart(key.clone());
+            if env.storage().persistent().has(&start_key) {
+                Self::extend_ttl(&env, &start_key);
+            }
+        }
+        let open_disputes_key = DataKey::OpenDisputes(key.clone());
+        if env.storage().persistent().has(&open_disputes_key) {
+            Self::extend_ttl(&env, &open_disputes_key);
+        }
+
+        let position_key = DataKey::IndexPosition(key);
+        if let Some(position) = env
+            .storage()
+            .persistent()
+            .get::<_, IndexPosition>(&position_key)
+        {
+            Self::extend_ttl(&env, &position_key);
+            Self::extend_ttl(
+                &env,
+                &DataKey::SubscriberIndex(subscriber.clone(), position.subscriber_pos),
+            );
+            Self::extend_ttl(&env, &DataKey::SubscriberIndexLen(subscriber));
+            Self::extend_ttl(
+                &env,
+                &DataKey::MerchantIndex(merchant.clone(), position.merchant_pos),
+            );
+            Self::extend_ttl(&env, &DataKey::MerchantIndexLen(merchant));
+        }
+    }
+
+    /// Extend the TTL of a user's debt record, unpaid advances and related
+    /// settings (anyone)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `user` - The user whose debt records to keep alive
+    pub fn bump_debt(env: Env, user: Address) {
+        Self::extend_instance_ttl(&env);
+
+        let user_debt = Self::load_user_debt(&env, &user).expect("No debt found for user");
+        for id in user_debt.next_unpaid..user_debt.advance_count {
+            let advance_key = DataKey::Advance(user.clone(), id);
+            let advance: BnplAdvance = env
+                .storage()
+                .persistent()
+                .get(&advance_key)
+                .expect("Advance not found");
+            Self::extend_ttl(&env, &advance_key);
+
+            // The funding vault's running total is touched on repayment
+            let total_key = DataKey::TotalDebt(advance.pool, advance.token);
+            if env.storage().persistent().has(&total_key) {
+                Self::extend_ttl(&env, &total_key);
+            }
+        }
+        Self::load_auto_repay(&env, &user);
+        Self::is_defaulted(&env, &user);
+    }
+
+    /// List a subscriber's live (not completed or cancelled) subscriptions
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    /// * `offset` - Index position of the first subscription to return
+    /// * `limit` - Maximum number of subscriptions to return (capped at 50)
+    ///
+    /// # Returns
+    /// * The subscriptions in the requested page
+    pub fn list_subscriptions_by_subscriber(
+        env: Env,
+        subscriber: Address,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<Subscription> {
+        let mut subscriptions = Vec::new(&env);
+        let len: u32 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::SubscriberIndexLen(subscriber.clone()))
+            .unwrap_or(0);
+
+        let end = offset.saturating_add(limit.min(MAX_PAGE_SIZE)).min(len);
+        for pos in offset..end {
+            let merchant: Address = env
+                .storage()
+                .persistent()
+                .get(&DataKey::SubscriberIndex(subscriber.clone(), pos))
+                .expect("Index entry not found");
+            let storage_key = DataKey::Subscription(SubscriptionKey {
+                subscriber: subscriber.clone(),
+                merchant,
+            });
+            if let Some(subscription) = Self::load_subscription(&env, &storage_key) {
+                subscriptions.push_back(subscription);
+            }
+        }
+        subscriptions
+    }
+
+    /// List the live (not completed or cancelled) subscriptions paying a merchant
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `merchant` - The merchant's address
+    /// * `offset` - Index position of the first subscription to return
+    /// * `limit` - Maximum number of subscriptions to return (capped at 50)
+    ///
+    /// # Returns
+    /// * The subscribers' subscriptions in the requested page
+    pub fn list_subscribers_by_merchant(
+        env: Env,
+        merchant: Address,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<Subscription> {
+        let mut subscriptions = Vec::new(&env);
+        let len: u32 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::MerchantIndexLen(merchant.clone()))
+            .unwrap_or(0);
+
+        let end = offset.saturating_add(limit.min(MAX_PAGE_SIZE)).min(len);
+        for pos in offset..end {
+            let subscriber: Address = env
+                .storage()
+                .persistent()
+                .get(&DataKey::MerchantIndex(merchant.clone(), pos))
+                .expect("Index entry not found");
+            let storage_key = DataKey::Subscription(SubscriptionKey {
+                subscriber,
+                merchant: merchant.clone(),
+            });
+            if let Some(subscription) = Self::load_subscription(&env, &storage_key) {
+                subscriptions.push_back(subscription);
+            }
+        }
+        subscriptions
+    }
+
+    /// List a user's BNPL advances, oldest first
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `user` - The user the advances were made for
+    /// * `offset` - Id of the first advance to return
+    /// * `limit` - Maximum number of advances to return (capped at 50)
+    ///
+    /// # Returns
+    /// * The advances in the requested page
+    pub fn get_advances(env: Env, user: Address, offset: u32, limit: u32) -> Vec<BnplAdvance> {
+        let mut advances = Vec::new(&env);
+        let advance_count = Self::load_user_debt(&env, &user)
+            .map(|debt| debt.advance_count)
+            .unwrap_or(0);
+
+        let end = offset
+            .saturating_add(limit.min(MAX_PAGE_SIZE))
+            .min(advance_count);
+        for id in offset..end {
+            if let Some(advance) = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Advance(user.clone(), id))
+            {
+                advances.push_back(advance);
+            }
+        }
+        advances
+    }
+
+    /// Get a single payment receipt by id
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address
+    /// * `id` - The receipt id
+    ///
+    /// # Returns
+    /// * The Receipt record
+    pub fn get_receipt(env: Env, subscriber: Address, merchant: Address, id: u32) -> Receipt {
+        let receipt_key = DataKey::Receipt(
+            SubscriptionKey {
+                subscriber,
+                merchant,
+            },
+            id,
+        );
+        let receipt = env
+            .storage()
+            .persistent()
+            .get(&receipt_key)
+            .expect("Receipt not found");
+        Self::extend_ttl(&env, &receipt_key);
+        receipt
+    }
+
+    /// List a subscription's payment receipts, oldest first
+    ///
+    /// The most recent 24 receipts are retained, along with any older ones
+    /// that can still be disputed, up to 48 in all; ids of deleted receipts
+    /// are skipped.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address
+    /// * `offset` - Id of the first receipt to return
+    /// * `limit` - Maximum number of ids to scan (capped at 50)
+    ///
+    /// # Returns
+    /// * The retained receipts in the requested range
+    pub fn get_receipts(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<Receipt> {
+        let key = SubscriptionKey {
+            subscriber,
+            merchant,
+        };
+        let receipt_count = Self::receipt_count(&env, &key);
+
+        let mut receipts = Vec::new(&env);
+        let end = offset
+            .saturating_add(limit.min(MAX_PAGE_SIZE))
+            .min(receipt_count);
+        for id in offset.max(Self::first_receipt(&env, &key))..end {
+            if let Some(receipt) = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Receipt(key.clone(), id))
+            {
+                receipts.push_back(receipt);
+            }
+        }
+        receipts
+    }
+
+    /// Repay BNPL debt
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `user` - The user repaying debt (must sign)
+    /// * `amount` - Amount to repay
+    ///
+    /// # Returns
+    /// * Remaining debt amount
+    pub fn repay_debt(env: Env, user: Address, amount: i128) -> i128 {
+        // User must authorize repayment
+        user.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if amount <= 0 {
+            panic!("Repayment amount must be positive");
+        }
+
+        let mut user_debt = Self::load_user_debt(&env, &user)
+            .filter(|debt| debt.amount > 0)
+            .expect("No debt found for user");
+
+        if amount > user_debt.amount {
+            panic!("Repayment amount exceeds debt");
+        }
+
+        // Transfer repayment from user to the pool it is owed to
+        Self::record_repayment(&env, &user, &user, &mut user_debt, amount);
+
+        user_debt.amount
+    }
+
+    /// Write off a user's defaulted BNPL debt (risk manager only)
+    ///
+    /// Debt is in default once its oldest unpaid advance is older than 90
+    /// days. The unpaid principal is covered from the pool's bad-debt reserve
+    /// first and any remainder is socialized across LP shares. The user is
+    /// barred from further BNPL advances.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `caller` - A risk manager (must sign)
+    /// * `user` - The defaulted user
+    ///
+    /// # Returns
+    /// * The total debt written off (principal and fees)
+    pub fn write_off_debt(env: Env, caller: Address, user: Address) -> i128 {
+        Self::require_role(&env, &caller, Role::RiskManager);
+
+        let debt_key = DataKey::UserDebt(user.clone());
+        let mut user_debt = Self::load_user_debt(&env, &user)
+            .filter(|debt| debt.amount > 0)
+            .expect("No debt found for user");
+
+        // Mark every outstanding advance as written off, collecting the unpaid
+        // principal. Each vault absorbs the loss on the advances it funded:
+        // reserve first, then LPs.
+        let default_period = Self::load_config(&env).default_period;
+        let current_time = env.ledger().timestamp();
+        let mut covered = 0;
+        let mut run: Option<Address> = None;
+        let mut principal = 0;
+        for id in user_debt.next_unpaid..user_debt.advance_count {
+            let advance_key = DataKey::Advance(user.clone(), id);
+            let mut advance: BnplAdvance = env
+                .storage()
+                .persistent()
+                .get(&advance_key)
+                .expect("Advance not found");
+
+            if id == user_debt.next_unpaid && current_time < advance.timestamp + default_period {
+                panic!("Debt is not in default");
+            }
+
+            // Debt carried over from v1 was never lent by the vault
+            if !Self::is_legacy_advance(&env, &advance) {
+                if run.as_ref() != Some(&advance.pool) {
+                    if let Some(pool) = run.replace(advance.pool.clone()) {
+                        covered +=
+                            Self::write_off_with_pool(&env, &pool, &advance.token, principal);
+                    }
+                    principal = 0;
+                }
+                principal += (advance.amount - advance.repaid).max(0);
+            }
+            advance.written_off = true;
+            env.storage().persistent().set(&advance_key, &advance);
+            Self::extend_ttl(&env, &advance_key);
+        }
+        if let Some(pool) = run {
+            covered += Self::write_off_with_pool(&env, &pool, &user_debt.token, principal);
+        }
+
+        let written_off = user_debt.amount;
+        user_debt.amount = 0;
+        user_debt.next_unpaid = user_debt.advance_count;
+        env.storage().persistent().set(&debt_key, &user_debt);
+        Self::extend_ttl(&env, &debt_key);
+
+        let defaulted_key = DataKey::Defaulted(user.clone());
+        env.storage().persistent().set(&defaulted_key, &true);
+        Self::extend_ttl(&env, &defaulted_key);
+
+        log!(
+            &env,
+            "Bad debt written off: {} tokens for {} ({} covered by reserve)",
+            written_off,
+            user,
+            covered
+        );
+
+        DebtWrittenOff {
+            user,
+            token: user_debt.token.clone(),
+            version: EVENT_VERSION,
+            written_off,
+            principal,
+            covered,
+            uncovered: principal - covered,
+        }
+        .publish(&env);
+
+        written_off
+    }
+
+    /// Opt in to (or out of) automatic debt repayment on collections
+    ///
+    /// When enabled, every successful `collect_payment` for this user also
+    /// pulls up to `portion_bps` of the outstanding debt toward the liquidity
+    /// pool, limited by the surplus balance and remaining allowance.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `user` - The user configuring auto-repay (must sign)
+    /// * `portion_bps` - Portion of outstanding debt per collection in basis points (0 disables)
+    pub fn set_auto_repay(env: Env, user: Address, portion_bps: u32) {
+        user.require_auth();
+        Self::extend_instance_ttl(&env);
+
+        if portion_bps > BPS_DENOMINATOR {
+            panic!("Auto-repay portion exceeds 100%");
+        }
+
+        let key = DataKey::AutoRepay(user);
+        if portion_bps == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &portion_bps);
+            Self::extend_ttl(&env, &key);
+        }
+    }
+
+    /// Get the user's auto-repay portion in basis points (0 if disabled)
+    pub fn get_auto_repay(env: Env, user: Address) -> u32 {
+        Self::load_auto_repay(&env, &user)
+    }
+
+    /// Get the user's credit score from their subscription
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    /// * `merchant` - The merchant's address
+    ///
+    /// # Returns
+    /// * The credit score (u32)
+    pub fn get_credit_score(env: Env, subscriber: Address, merchant: Address) -> u32 {
+        let key = SubscriptionKey {
+            subscriber,
+            merchant,
+        };
+        let storage_key = DataKey::Subscription(key);
+
+        let subscription =
+            Self::load_subscription(&env, &storage_key).expect("Subscription not found");
+
+        subscription.credit_score
+    }
+
+    /// Get the summary of a subscriber's purged and replaced subscriptions
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriber` - The subscriber's address
+    ///
+    /// # Returns
+    /// * The CreditHistory (all zero if nothing has been summarized yet)
+    pub fn get_credit_history(env: Env, subscriber: Address) -> CreditHistory {
+        Self::load_credit_history(&env, &subscriber)
+    }
+
+    /// Delete finished subscriptions to reclaim their storage (anyone)
+    ///
+    /// A subscription is finished once it has been cancelled or completed
+    /// and its last paid period has ended. Each purged record is folded into the
+    /// subscriber's credit history first; its receipts are deleted with it.
+    /// Active, unfinished and missing subscriptions are skipped, as are
+    /// subscriptions with an open dispute.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `subscriptions` - Subscriptions to purge (at most 50 per call)
+    ///
+    /// # Returns
+    /// * The number of subscriptions that were purged
+    pub fn purge_subscriptions(env: Env, subscriptions: Vec<SubscriptionKey>) -> u32 {
+        Self::extend_instance_ttl(&env);
+
+        if subscriptions.len() > MAX_PAGE_SIZE {
+            panic!("Purge batch too large");
+        }
+
+        let mut purged = 0;
+        for key in subscriptions.iter() {
+            let storage_key = DataKey::Subscription(key.clone());
+            let subscription = match Self::load_subscription(&env, &storage_key) {
+                Some(subscription) => subscription,
+                None => continue,
+            };
+            if subscription.status.is_live()
+                || !Self::is_finished(&env, &subscription)
+                || Self::in_dispute_window(&env, &key)
+                || Self::load_open_disputes(&env, &key) > 0
+            {
+                continue;
+            }
+
+            Self::record_credit_history(&env, &subscription);
+            env.storage().persistent().remove(&storage_key);
+            Self::remove_receipts(&env, &key);
+            Self::remove_seat_plan(&env, &key);
+            purged += 1;
+
+            SubscriptionPurged {
+                subscriber: key.subscriber,
+                merchant: key.merchant,
+                version: EVENT_VERSION,
+                credit_score: subscription.credit_score,
+            }
+            .publish(&env);
+        }
+
+        purged
+    }
+
+    /// Get the liquidity pool address
+    pub fn get_liquidity_pool(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::LiquidityPool)
+            .expect("Liquidity pool not initialized")
+    }
+
+    /// Get liquidity pool health for a token
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `token` - The token to report on
+    ///
+    /// # Returns
+    /// * Vault cash, outstanding BNPL debt and utilization against the limit
+    pub fn get_pool_health(env: Env, token: Address) -> PoolHealth {
+        let liquidity_pool: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityPool)
+            .expect("Liquidity pool not initialized");
+        let reserves = LiquidityPoolClient::new(&env, &liquidity_pool).get_reserves(&token);
+        let outstanding_debt = Self::total_debt(&env, &liquidity_pool, &token);
+
+        PoolHealth {
+            cash: reserves.cash,
+            outstanding_debt,
+            utilization_bps: Self::utilization_bps(outstanding_debt, reserves.cash),
+            max_utilization_bps: Self::load_config(&env).max_utilization_bps,
+        }
+    }
+
+    /// Get the admin address
+    pub fn get_admin(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Contract not initialized")
+    }
+
+    /// Propose a new admin (admin only)
+    ///
+    /// The proposal must be accepted by the new admin within 7 days. A new
+    /// proposal replaces any pending one.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `new_admin` - The proposed admin address
+    ///
+    /// # Returns
+    /// * The pending proposal
+    pub fn propose_admin(env: Env, new_admin: Address) -> PendingAdmin {
+        let admin = Self::require_admin(&env);
+
+        let pending = PendingAdmin {
+            address: new_admin,
+            expires_at: env.ledger().timestamp() + ADMIN_PROPOSAL_PERIOD,
+        };
+        env.storage()
+            .instance()
+            .set(&DataKey::PendingAdmin, &pending);
+
+        AdminProposed {
+            version: EVENT_VERSION,
+            admin,
+            new_admin: pending.address.clone(),
+            expires_at: pending.expires_at,
+        }
+        .publish(&env);
+
+        pending
+    }
+
+    /// Accept a pending admin proposal (proposed admin must sign)
+    pub fn accept_admin(env: Env) {
+        Self::extend_instance_ttl(&env);
+        let pending: PendingAdmin = env
+            .storage()
+            .instance()
+            .get(&DataKey::PendingAdmin)
+            .expect("No pending admin proposal");
+        pending.address.require_auth();
+
+        if env.ledger().timestamp() > pending.expires_at {
+            panic!("Admin proposal expired");
+        }
+
+        let previous_admin = Self::get_admin(env.clone());
+        env.storage()
+            .instance()
+            .set(&DataKey::Admin, &pending.address);
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+
+        AdminAccepted {
+            version: EVENT_VERSION,
+            previous_admin,
+            new_admin: pending.address,
+        }
+        .publish(&env);
+    }
+
+    /// Withdraw a pending admin proposal (admin only)
+    pub fn cancel_admin_proposal(env: Env) {
+        let admin = Self::require_admin(&env);
+
+        let pending: PendingAdmin = env
+            .storage()
+            .instance()
+            .get(&DataKey::PendingAdmin)
+            .expect("No pending admin proposal");
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+
+        AdminProposalCancelled {
+            version: EVENT_VERSION,
+            admin,
+            proposed: pending.address,
+        }
+        .publish(&env);
+    }
+
+    /// Get the pending admin proposal, if any
+    pub fn get_pending_admin(env: Env) -> Option<PendingAdmin> {
+        env.storage().instance().get(&DataKey::PendingAdmin)
+    }
+
+    /// Grant an operator role to an address (admin only)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `role` - The role to grant
+    /// * `account` - The address receiving the role
+    pub fn grant_role(env: Env, role: Role, account: Address) {
+        let admin = Self::require_admin(&env);
+
+        if role == Role::Admin {
+            panic!("Admin role is transferred with propose_admin");
+        }
+
+        let key = DataKey::Role(role, account.clone());
+        env.storage().persistent().set(&key, &true);
+        Self::extend_ttl(&env, &key);
+
+        RoleGranted {
+            version: EVENT_VERSION,
+            admin,
+            role,
+            account,
+        }
+        .publish(&env);
+    }
+
+    /// Revoke an operator role from an address (admin only)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `role` - The role to revoke
+    /// * `account` - The address losing the role
+    pub fn revoke_role(env: Env, role: Role, account: Address) {
+        let admin = Self::require_admin(&env);
+
+        let key = DataKey::Role(role, account.clone());
+        if !env.storage().persistent().has(&key) {
+            panic!("Role not granted");
+        }
+        env.storage().persistent().remove(&key);
+
+        RoleRevoked {
+            version: EVENT_VERSION,
+            admin,
+            role,
+            account,
+        }
+        .publish(&env);
+    }
+
+    /// Check whether an address holds a role (the admin holds every role)
+    pub fn has_role(env: Env, role: Role, account: Address) -> bool {
+        if account == Self::get_admin(env.clone()) {
+            return true;
+        }
+        let key = DataKey::Role(role, account);
+        let granted = env.storage().persistent().has(&key);
+        if granted {
+            Self::extend_ttl(&env, &key);
+        }
+        granted
+    }
+
+    /// Set the emergency pause switches
+    ///
+    /// The admin may set any combination. Pausers may only turn switches
+    /// on; lifting a pause needs the admin.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `caller` - The admin or a pauser (must sign)
+    /// * `flags` - The new pause switches
+    pub fn set_pause(env: Env, caller: Address, flags: PauseFlags) {
+        Self::require_role(&env, &caller, Role::Pauser);
+
+        if caller != Self::get_admin(env.clone()) {
+            let current = Self::load_pause(&env);
+            if (current.subscriptions && !flags.subscriptions)
+                || (current.collections && !flags.collections)
+                || (current.bnpl && !flags.bnpl)
+            {
+                panic!("Pauser can only pause");
+            }
+        }
+
+        env.storage().instance().set(&DataKey::Pause, &flags);
+
+        PauseUpdated {
+            version: EVENT_VERSION,
+            caller,
+            flags,
+        }
+        .publish(&env);
+    }
+
+    /// Get the emergency pause switches
+    pub fn get_pause(env: Env) -> PauseFlags {
+        Self::load_pause(&env)
+    }
+
+    /// Queue a parameter change behind the timelock
+    ///
+    /// The change can be executed by anyone once 48 hours have passed and
+    /// cancelled until then. Config changes need the risk manager role;
+    /// liquidity pool, protocol fee and merchant fee changes need the
+    /// treasurer role; settlement delays need a merchant verifier. Only the
+    /// admin can change the treasury.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `caller` - An operator holding the role for this change (must sign)
+    /// * `change` - The parameter change to queue
+    ///
+    /// # Returns
+    /// * The queued change
+    pub fn queue_param_change(env: Env, caller: Address, change: ParamChange) -> PendingChange {
+        Self::require_role(&env, &caller, Self::param_change_role(&change));
+
+        if let ParamChange::Config(config) = &change {
+            if config.max_utilization_bps > BPS_DENOMINATOR
+                || config.bnpl_fee_bps > BPS_DENOMINATOR
+                || config.reserve_share_bps > BPS_DENOMINATOR
+            {
+                panic!("Basis-point value exceeds 100%");
+            }
+        }
+        if let ParamChange::ProtocolFee(fee_bps) | ParamChange::MerchantFee(_, Some(fee_bps)) =
+            &change
+        {
+            if *fee_bps > MAX_PROTOCOL_FEE_BPS {
+                panic!("Protocol fee too high");
+            }
+        }
+        if let ParamChange::SettlementDelay(_, delay) = &change {
+            if *delay > MAX_SETTLEMENT_DELAY {
+                panic!("Settlement delay too long");
+            }
+        }
+
+        let id: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NextParamChange)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKey::NextParamChange, &(id + 1));
+
+        let pending = PendingChange {
+            id,
+            change,
+            proposer: caller.clone(),
+            execute_after: env.ledger().timestamp() + PARAM_CHANGE_DELAY,
+        };
+        let key = DataKey::ParamChange(id);
+        env.storage().persistent().set(&key, &pending);
+        Self::extend_ttl(&env, &key);
+
+        ParamChangeQueued {
+            version: EVENT_VERSION,
+            caller,
+            id,
+            change: pending.change.clone(),
+            execute_after: pending.execute_after,
+        }
+        .publish(&env);
+
+        pending
+    }
+
+    /// Cancel a queued parameter change before it is executed
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `caller` - An operator holding the role for this change (must sign)
+    /// * `id` - The queued change id
+    pub fn cancel_param_change(env: Env, caller: Address, id: u32) {
+        let key = DataKey::ParamChange(id);
+        let pending: PendingChange = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Parameter change not found");
+        Self::require_role(&env, &caller, Self::param_change_role(&pending.change));
+
+        env.storage().persistent().remove(&key);
+
+        ParamChangeCancelled {
+            version: EVENT_VERSION,
+            caller,
+            id,
+        }
+        .publish(&env);
+    }
+
+    /// Apply a queued parameter change once its delay has passed (anyone)
+    ///
+    /// A liquidity pool rotation waits until all BNPL debt has been repaid
+    /// or written off, since advances are repaid to the vault that funded them.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `id` - The queued change id
+    pub fn execute_param_change(env: Env, id: u32) {
+        Self::extend_instance_ttl(&env);
+        let key = DataKey::ParamChange(id);
+        let pending: PendingChange = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Parameter change not found");
+
+        if env.ledger().timestamp() < pending.execute_after {
+            panic!("Parameter change is still timelocked");
+        }
+
+        env.storage().persistent().remove(&key);
+
+        match pending.change {
+            ParamChange::Config(config) => {
+                env.storage().instance().set(&DataKey::Config, &config);
+
+                ConfigUpdated {
+                    version: EVENT_VERSION,
+                    proposer: pending.proposer,
+                    config,
+                }
+                .publish(&env);
+            }
+            ParamChange::LiquidityPool(liquidity_pool) => {
+                // Outstanding advances stay owed to the vault that funded them
+                if Self::schema_version(&env) == 1 {
+                    Self::legacy_pool(&env);
+                }
+
+                env.storage()
+                    .instance()
+                    .set(&DataKey::LiquidityPool, &liquidity_pool);
+
+                LiquidityPoolUpdated {
+                    version: EVENT_VERSION,
+                    proposer: pending.proposer,
+                    liquidity_pool,
+                }
+                .publish(&env);
+            }
+            ParamChange::ProtocolFee(fee_bps) => {
+                env.storage()
+                    .instance()
+                    .set(&DataKey::ProtocolFee, &fee_bps);
+
+                ProtocolFeeUpdated {
+                    version: EVENT_VERSION,
+                    proposer: pending.proposer,
+                    fee_bps,
+                }
+                .publish(&env);
+            }
+            ParamChange::Treasury(treasury) => {
+                env.storage().instance().set(&DataKey::Treasury, &treasury);
+
+                TreasuryUpdated {
+                    version: EVENT_VERSION,
+                    proposer: pending.proposer,
+                    treasury,
+                }
+                .publish(&env);
+            }
+            ParamChange::MerchantFee(merchant, fee_bps) => {
+                let key = DataKey::MerchantFee(merchant.clone());
+                match fee_bps {
+                    Some(fee_bps) => {
+                        env.storage().persistent().set(&key, &fee_bps);
+                        Self::extend_ttl(&env, &key);
+                    }
+                    None => env.storage().persistent().remove(&key),
+                }
+
+                MerchantFeeUpdated {
+                    version: EVENT_VERSION,
+                    proposer: pending.proposer,
+                    merchant,
+                    fee_bps,
+                }
+                .publish(&env);
+            }
+            ParamChange::SettlementDelay(merchant, delay) => {
+                // Only affects payments collected from now on
+                let key = DataKey::SettlementDelay(merchant.clone());
+                if delay > 0 {
+                    env.storage().persistent().set(&key, &delay);
+                    Self::extend_ttl(&env, &key);
+                } else {
+                    env.storage().persistent().remove(&key);
+                }
+
+                SettlementDelayUpdated {
+                    version: EVENT_VERSION,
+                    proposer: pending.proposer,
+                    merchant,
+                    delay,
+                }
+                .publish(&env);
+            }
+        }
+    }
+
+    /// Get a queued parameter change, if it is still pending
+    pub fn get_param_change(env: Env, id: u32) -> Option<PendingChange> {
+        env.storage().persistent().get(&DataKey::ParamChange(id))
+    }
+
+    /// List queued parameter changes that are still pending, oldest first
+    ///
+    /// Ids of executed and cancelled changes are skipped.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `offset` - Id of the first change to return
+    /// * `limit` - Maximum number of ids to scan (capped at 50)
+    ///
+    /// # Returns
+    /// * The pending changes in the requested range
+    pub fn list_param_changes(env: Env, offset: u32, limit: u32) -> Vec<PendingChange> {
+        let next_id: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NextParamChange)
+            .unwrap_or(0);
+
+        let mut changes = Vec::new(&env);
+        let end = offset.saturating_add(limit.min(MAX_PAGE_SIZE)).min(next_id);
+        for id in offset..end {
+            if let Some(pending) = env.storage().persistent().get(&DataKey::ParamChange(id)) {
+                changes.push_back(pending);
+            }
+        }
+        changes
+    }
+
+    /// Get the current risk and credit parameters
+    pub fn get_config(env: Env) -> Config {
+        Self::load_config(&env)
+    }
+
+    /// Mark a merchant as verified or not (merchant verifier only)
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `caller` - A merchant verifier (must sign)
+    /// * `merchant` - The merchant being vetted
+    /// * `verified` - Whether the merchant passed verification
+    pub fn set_merchant_verified(env: Env, caller: Address, merchant: Address, verified: bool) {
+        Self::require_role(&env, &caller, Role::MerchantVerifier);
+
+        let key = DataKey::VerifiedMerchant(merchant.clone());
+        if verified {
+            env.storage().persistent().set(&key, &true);
+            Self::extend_ttl(&env, &key);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+
+        MerchantVerified {
+            version: EVENT_VERSION,
+            caller,
+            merchant,
+            verified,
+        }
+        .publish(&env);
+    }
+
+    /// Check whether a merchant has been verified
+    pub fn is_merchant_verified(env: Env, merchant: Address) -> bool {
+        let key = DataKey::VerifiedMerchant(merchant);
+        let verified = env.storage().persistent().has(&key);
+        if verified {
+            Self::extend_ttl(&env, &key);
+        }
+        verified
+    }
+
+    /// Set or clear a merchant's dedicated arbiter (merchant verifier only)
+    ///
+    /// While set, only this arbiter can resolve the merchant's disputes.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `caller` - A merchant verifier (must sign)
+    /// * `merchant` - The merchant concerned
+    /// * `arbiter` - The dedicated arbiter, or None to use the global arbiters
+    pub fn set_merchant_arbiter(
+        env: Env,
+        caller: Address,
+        merchant: Address,
+        arbiter: Option<Address>,
+    ) {
+        Self::require_role(&env, &caller, Role::MerchantVerifier);
+
+        let key = DataKey::MerchantArbiter(merchant.clone());
+        match &arbiter {
+            Some(arbiter) => {
+                env.storage().persistent().set(&key, arbiter);
+                Self::extend_ttl(&env, &key);
+            }
+            None => env.storage().persistent().remove(&key),
+        }
+
+        MerchantArbiterUpdated {
+            version: EVENT_VERSION,
+            caller,
+            merchant,
+            arbiter,
+        }
+        .publish(&env);
+    }
+
+    /// Get a merchant's dedicated arbiter, if one is set
+    pub fn get_merchant_arbiter(env: Env, merchant: Address) -> Option<Address> {
+        let key = DataKey::MerchantArbiter(merchant);
+        let arbiter = env.storage().persistent().get(&key);
+        if arbiter.is_some() {
+            Self::extend_ttl(&env, &key);
+        }
+        arbiter
+    }
+
+    /// Get the address receiving protocol fees, if set
+    pub fn get_treasury(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::Treasury)
+    }
+
+    /// Get the protocol fee on collections in basis points
+    pub fn get_protocol_fee(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::ProtocolFee)
+            .unwrap_or(0)
+    }
+
+    /// Get the protocol fee charged on a merchant's collections in basis points
+    pub fn get_merchant_fee(env: Env, merchant: Address) -> u32 {
+        Self::load_merchant_fee(&env, &merchant)
+    }
+
+    /// Get the protocol fees collected so far in a token
+    pub fn get_protocol_fees(env: Env, token: Address) -> i128 {
+        let key = DataKey::ProtocolFees(token);
+        let total = env.storage().persistent().get(&key);
+        if total.is_some() {
+            Self::extend_ttl(&env, &key);
+        }
+        total.unwrap_or(0)
+    }
+
+    /// Get a merchant's settlement delay in seconds (0 if paid out directly)
+    pub fn get_settlement_delay(env: Env, merchant: Address) -> u64 {
+        Self::load_settlement_delay(&env, &merchant)
+    }
+
+    /// Replace the contract's WASM (admin only)
+    ///
+    /// Storage is kept as-is. If the new code bumps the storage schema, call
+    /// `migrate` afterwards.
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `new_wasm_hash` - Hash of the already-uploaded WASM to run from now on
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) {
+        let admin = Self::require_admin(&env);
+
+        env.deployer()
+            .update_current_contract_wasm(new_wasm_hash.clone());
+
+        Upgraded {
+            version: EVENT_VERSION,
+            admin,
+            new_wasm_hash,
+        }
+        .publish(&env);
+    }
+
+    /// Bring storage up to the current schema version (admin only)
+    ///
+    /// Records in an older layout are also converted lazily the first time
+    /// they are read, so this only has to list the records that should be
+    /// rewritten eagerly (at most 50 per call).
+    ///
+    /// # Arguments
+    /// * `env` - The contract environment
+    /// * `users` - Users whose debt records should be converted
+    /// * `subscriptions` - Subscriptions that should be converted
+    ///
+    /// # Returns
+    /// * The number of records that were converted
+    pub fn migrate(env: Env, users: Vec<Address>, subscriptions: Vec<SubscriptionKey>) -> u32 {
+        let admin = Self::require_admin(&env);
+
+        if users.len() + subscriptions.len() > MAX_PAGE_SIZE {
+            panic!("Migration batch too large");
+        }
+
+        let mut migrated = 0;
+        for user in users.iter() {
+            let debt_key = DataKey::UserDebt(user.clone());
+            if let Some(raw) = env.storage().persistent().get::<_, Val>(&debt_key) {
+                if !Self::is_current_layout(&env, &raw, USER_DEBT_FIELDS) {
+                    // Loading converts and rewrites the record
+                    Self::load_user_debt(&env, &user);
+                    migrated += 1;
+                }
+            }
+        }
+        for key in subscriptions.iter() {
+            let storage_key = DataKey::Subscription(key.clone());
+            if let Some(raw) = env.storage().persistent().get::<_, Val>(&storage_key) {
+                let subscription = Self::load_subscription(&env, &storage_key).unwrap();
+                if !Self::is_current_layout(&env, &raw, SUBSCRIPTION_FIELDS) {
+                    env.storage().persistent().set(&storage_key, &subscription);
+                    Self::extend_ttl(&env, &storage_key);
+                    migrated += 1;
+                }
+
+                // Subscriptions created before v3 are missing from the indexes
+                if subscription.status.is_live() {
+                    Self::index_subscription(&env, &key);
+                }
+            }
+        }
+
+        let from_version = Self::schema_version(&env);
+        if from_version == 1 {
+            Self::legacy_pool(&env);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &SCHEMA_VERSION);
+
+        log!(
+            &env,
+            "Migrated {} records, schema {} -> {}",
+            migrated,
+            from_version,
+            SCHEMA_VERSION
+        );
+
+        Migrated {
+            version: EVENT_VERSION,
+            admin,
+            from_version,
+            to_version: SCHEMA_VERSION,
+            migrated,
+        }
+        .publish(&env);
+
+        migrated
+    }
+
+    /// Get the storage schema version
+    pub fn get_schema_version(env: Env) -> u32 {
+        Self::schema_version(&env)
+    }
+
+    /// Check if contract is initialized
+    pub fn is_initialized(env: Env) -> bool {
+        env.storage().instance().has(&DataKey::Initialized)
+    }
+}
+
+// ============================================================================
+// INTERNAL HELPERS
+// ============================================================================
+
+impl SoroSubContract {
+    /// Top up the contract instance TTL (admin, config, pause switches, pool address)
+    fn extend_instance_ttl(env: &Env) {
+        env.storage()
+            .instance()
+            .extend_ttl(TTL_THRESHOLD_LEDGERS, THIRTY_DAYS_LEDGERS);
+    }
+
+    /// Top up the TTL of an existing persistent entry
+    fn extend_ttl(env: &Env, key: &DataKey) {
+        env.storage()
+            .persistent()
+            .extend_ttl(key, TTL_THRESHOLD_LEDGERS, THIRTY_DAYS_LEDGERS);
+    }
+
+    /// Load the stored admin and require its authorization
+    fn require_admin(env: &Env) -> Address {
+        Self::extend_instance_ttl(env);
+        let admin: Address = env
             .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .expect("Contract not initialized");
+        admin.require_auth();
+        admin
+    }
+
+    /// Require `caller`'s authorization and that it holds `role`
+    fn require_role(env: &Env, caller: &Address, role: Role) {
+        Self::extend_instance_ttl(env);
+        caller.require_auth();
+        if !Self::has_role(env.clone(), role, caller.clone()) {
+            panic!("Caller does not have the required role");
+        }
+    }
+
+    /// Role needed to queue or cancel a parameter change
+    fn param_change_role(change: &ParamChange) -> Role {
+        match change {
+            ParamChange::Config(_) => Role::RiskManager,
+            ParamChange::LiquidityPool(_)
+            | ParamChange::ProtocolFee(_)
+            | ParamChange::MerchantFee(..) => Role::Treasurer,
+            ParamChange::SettlementDelay(..) => Role::MerchantVerifier,
+            ParamChange::Treasury(_) => Role::Admin,
+        }
+    }
+
+    /// Auto-repay portion in basis points (0 if not opted in)
+    fn load_auto_repay(env: &Env, user: &Address) -> u32 {
+        let key = DataKey::AutoRepay(user.clone());
+        match env.storage().persistent().get(&key) {
+            Some(portion_bps) => {
+                Self::extend_ttl(env, &key);
+                portion_bps
+            }
+            None => 0,
+        }
+    }
+
+    /// Whether the user had BNPL debt written off
+    fn is_defaulted(env: &Env, user: &Address) -> bool {
+        let key = DataKey::Defaulted(user.clone());
+        let defaulted = env.storage().persistent().has(&key);
+        if defaulted {
+            Self::extend_ttl(env, &key);
+        }
+        defaulted
+    }
+
+    /// Stored configuration, or the defaults if it was never updated
+    fn load_config(env: &Env) -> Config {
+        env.storage()
+            .instance()
+            .get(&DataKey::Config)
+            .unwrap_or(Config {
+                credit_score_increment: CREDIT_SCORE_INCREMENT,
+                bnpl_min_credit_score: BNPL_MIN_CREDIT_SCORE,
+                max_utilization_bps: MAX_UTILIZATION_BPS,
+                bnpl_fee_bps: BNPL_FEE_BPS,
+                reserve_share_bps: RESERVE_SHARE_BPS,
+                default_period: DEFAULT_PERIOD,
+            })
+    }
+
+    /// Stored pause switches, or everything running if never set
+    fn load_pause(env: &Env) -> PauseFlags {
+        env.storage()
+            .instance()
+            .get(&DataKey::Pause)
+            .unwrap_or(PauseFlags {
+                subscriptions: false,
+                collections: false,
+                bnpl: false,
+            })
+    }
+
+    /// Stored schema version; deployments from before versioning are v1
+    fn schema_version(env: &Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::SchemaVersion)
+            .unwrap_or(1)
+    }
+
+    /// Whether a stored struct has the field count of the current layout
+    fn is_current_layout(env: &Env, raw: &Val, fields: u32) -> bool {
+        Map::<Symbol, Val>::from_val(env, raw).len() == fields
+    }
+
+    /// Store a new subscription, replacing a finished one to the same merchant
+    fn open_subscription(
+        env: &Env,
+        payer: Address,
+        key: SubscriptionKey,
+        token: Address,
+        amount: i128,
+        interval: u64,
+        trial_period: u64,
+    ) -> Subscription {
+        Self::extend_instance_ttl(env);
+
+        if Self::load_pause(env).subscriptions {
+            panic!("New subscriptions are paused");
+        }
+
+        // Validate inputs
+        if amount <= 0 {
+            panic!("Amount must be positive");
+        }
+        if interval == 0 {
+            panic!("Interval must be greater than 0");
+        }
+
+        let subscriber = key.subscriber.clone();
+        let merchant = key.merchant.clone();
+
+        // Check if subscription already exists
+        let storage_key = DataKey::Subscription(key.clone());
+        let mut last_payment_time = 0;
+        if let Some(existing) = Self::load_subscription(env, &storage_key) {
+            if existing.status.is_live() {
+                panic_with_error!(env, SubscriptionError::AlreadyExists);
+            }
+            if !Self::is_finished(env, &existing) {
+                last_payment_time = existing.last_payment_time;
+            }
+            Self::record_credit_history(env, &existing);
+            Self::remove_seat_plan(env, &key);
+        }
+
+        // Create the subscription (the first payment is due at starts_at)
+        let subscription = Subscription {
+            subscriber: subscriber.clone(),
+            payer: payer.clone(),
+            merchant: merchant.clone(),
+            token: token.clone(),
+            amount,
+            interval,
+            starts_at: env.ledger().timestamp() + trial_period,
+            last_payment_time,
+            status: if trial_period > 0 {
+                SubscriptionStatus::Trialing
+            } else {
+                SubscriptionStatus::Active
+            },
+            credit_score: 0, // Start with 0 credit score
+        };
+
+        // Store the subscription
+        env.storage().persistent().set(&storage_key, &subscription);
+
+        // Extend TTL for 30 days
+        Self::extend_ttl(env, &storage_key);
+
+        // List it under both parties
+        Self::index_subscription(env, &key);
+
+        // Log subscription creation
+        log!(
+            env,
+            "Subscription Created for {} to {}",
+            subscriber,
+            merchant
+        );
+
+        // Emit event
+        SubscriptionCreated {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            token,
+            amount,
+            interval,
+        }
+        .publish(env);
+        if payer != subscriber {
+            SubscriptionGifted {
+                subscriber,
+                merchant,
+                version: EVENT_VERSION,
+                payer,
+            }
+            .publish(env);
+        }
+
+        subscription
+    }
+
+    /// Load a subscription, move it to a new status and store it
+    fn update_status(
+        env: &Env,
+        subscriber: Address,
+        merchant: Address,
+        status: SubscriptionStatus,
+    ) {
+        Self::extend_instance_ttl(env);
+
+        let storage_key = DataKey::Subscription(SubscriptionKey {
+            subscriber,
+            merchant,
+        });
+        let mut subscription =
+            Self::load_subscription(env, &storage_key).expect("Subscription not found");
+        Self::change_status(env, &mut subscription, status);
+        env.storage().persistent().set(&storage_key, &subscription);
+        Self::extend_ttl(env, &storage_key);
+    }
+
+    /// Whether a subscription's last paid period has ended
+    fn is_finished(env: &Env, subscription: &Subscription) -> bool {
+        subscription.last_payment_time == 0
+            || env.ledger().timestamp() >= subscription.last_payment_time + subscription.interval
+    }
+
+    /// Whether a subscription currently gives access (see `has_access`)
+    fn is_paid_up(env: &Env, subscription: &Subscription) -> bool {
+        let now = env.ledger().timestamp();
+        let paid_through = subscription.last_payment_time + subscription.interval;
+        match subscription.status {
+            SubscriptionStatus::Trialing => now < subscription.starts_at,
+            // Never-paid subscriptions get no grace period
+            SubscriptionStatus::Active | SubscriptionStatus::PastDue => {
+                subscription.last_payment_time > 0 && now < paid_through + PAST_DUE_GRACE_PERIOD
+            }
+            SubscriptionStatus::Cancelled | SubscriptionStatus::Completed => {
+                !Self::is_finished(env, subscription)
+            }
+            SubscriptionStatus::Paused | SubscriptionStatus::Suspended => false,
+        }
+    }
+
+    /// When the next payment of a subscription is due
+    fn next_payment_time(subscription: &Subscription) -> u64 {
+        if subscription.last_payment_time == 0 {
+            subscription.starts_at
+        } else {
+            subscription.last_payment_time + subscription.interval
+        }
+    }
+
+    /// Move a subscription to a new status, rejecting transitions the state
+    /// machine doesn't allow. Every change except cancellation emits
+    /// `StatusChanged`.
+    fn change_status(env: &Env, subscription: &mut Subscription, status: SubscriptionStatus) {
+        let from = subscription.status;
+        if !from.can_transition_to(status) {
+            panic_with_error!(env, SubscriptionError::InvalidTransition);
+        }
+        subscription.status = status;
+
+        if status != SubscriptionStatus::Cancelled {
+            StatusChanged {
+                subscriber: subscription.subscriber.clone(),
+                merchant: subscription.merchant.clone(),
+                version: EVENT_VERSION,
+                from,
+                to: status,
+            }
+            .publish(env);
+        }
+    }
+
+    /// Load a subscriber's credit history (zeroed if there is none)
+    fn load_credit_history(env: &Env, subscriber: &Address) -> CreditHistory {
+        let history_key = DataKey::CreditHistory(subscriber.clone());
+        match env.storage().persistent().get(&history_key) {
+            Some(history) => {
+                Self::extend_ttl(env, &history_key);
+                history
+            }
+            None => CreditHistory::default(),
+        }
+    }
+
+    /// Fold a finished subscription into its subscriber's credit history
+    fn record_credit_history(env: &Env, subscription: &Subscription) {
+        let mut history = Self::load_credit_history(env, &subscription.subscriber);
+        history.closed_subscriptions += 1;
+        history.total_credit_score += subscription.credit_score;
+        history.best_credit_score = history.best_credit_score.max(subscription.credit_score);
+        history.last_payment_time = history
+            .last_payment_time
+            .max(subscription.last_payment_time);
+
+        let history_key = DataKey::CreditHistory(subscription.subscriber.clone());
+        env.storage().persistent().set(&history_key, &history);
+        Self::extend_ttl(env, &history_key);
+    }
+
+    /// Number of receipts ever recorded for a subscription (next receipt id)
+    fn receipt_count(env: &Env, key: &SubscriptionKey) -> u32 {
+        env.storage()
             .persistent()
-            .get(&storage_key)
-            .expect("Subscription not found");
+            .get(&DataKey::ReceiptCount(key.clone()))
+            .unwrap_or(0)
+    }
 
-        subscription.credit_score
+    /// Load a team subscription's seats, if it is one
+    fn load_seat_plan(env: &Env, key: &SubscriptionKey) -> Option<SeatPlan> {
+        let plan_key = DataKey::SeatPlan(key.clone());
+        let plan = env.storage().persistent().get(&plan_key);
+        if plan.is_some() {
+            Self::extend_ttl(env, &plan_key);
+        }
+        plan
     }
 
-    /// Get the liquidity pool address
-    pub fn get_liquidity_pool(env: Env) -> Address {
+    /// Bill a team subscription's scheduled seat count and price from now on,
+    /// once the collection charging for them has been funded
+    fn apply_seat_change(
+        env: &Env,
+        key: &SubscriptionKey,
+        plan: Option<SeatPlan>,
+        subscription: &mut Subscription,
+    ) {
+        let Some(mut plan) = plan else {
+            return;
+        };
+        if plan.next_seats == plan.seats && plan.next_price_per_seat == plan.price_per_seat {
+            return;
+        }
+
+        plan.seats = plan.next_seats;
+        plan.price_per_seat = plan.next_price_per_seat;
+        subscription.amount = plan.price_per_seat * plan.seats as i128;
+        env.storage()
+            .persistent()
+            .set(&DataKey::SeatPlan(key.clone()), &plan);
+    }
+
+    /// Delete a team subscription's seats and its members' memberships
+    fn remove_seat_plan(env: &Env, key: &SubscriptionKey) {
+        let plan_key = DataKey::SeatPlan(key.clone());
+        let Some(plan) = env.storage().persistent().get::<_, SeatPlan>(&plan_key) else {
+            return;
+        };
+        for member in plan.members.iter() {
+            env.storage()
+                .persistent()
+                .remove(&DataKey::Membership(member, key.merchant.clone()));
+        }
+        env.storage().persistent().remove(&plan_key);
+    }
+
+    /// Address paying for a subscription (the subscriber if it is gone)
+    fn load_payer(env: &Env, key: &SubscriptionKey) -> Address {
+        Self::load_subscription(env, &DataKey::Subscription(key.clone()))
+            .map_or_else(|| key.subscriber.clone(), |subscription| subscription.payer)
+    }
+
+    /// Pay a refund from `from` to the payer. Refunds of pool-funded
+    /// payments repay the payer's outstanding BNPL debt first. Returns
+    /// the portion applied to debt.
+    fn pay_refund(
+        env: &Env,
+        from: &Address,
+        payer: &Address,
+        token: &Address,
+        source: &FundingSource,
+        amount: i128,
+    ) -> i128 {
+        let token_client = token::Client::new(env, token);
+        let mut to_debt = 0;
+        if let FundingSource::Bnpl(_) = source {
+            if let Some(mut user_debt) = Self::load_user_debt(env, payer)
+                .filter(|debt| debt.amount > 0 && debt.token == *token)
+            {
+                to_debt = amount.min(user_debt.amount);
+                Self::record_repayment(env, from, payer, &mut user_debt, to_debt);
+            }
+        }
+        if amount > to_debt {
+            token_client.transfer(from, payer, &(amount - to_debt));
+        }
+        to_debt
+    }
+
+    /// Number of open disputes against a subscription's payments
+    fn load_open_disputes(env: &Env, key: &SubscriptionKey) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::OpenDisputes(key.clone()))
+            .unwrap_or(0)
+    }
+
+    fn save_open_disputes(env: &Env, key: &SubscriptionKey, count: u32) {
+        let key = DataKey::OpenDisputes(key.clone());
+        if count == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &count);
+            Self::extend_ttl(env, &key);
+        }
+    }
+
+    /// Record a receipt for a collected payment, deleting old receipts that
+    /// fall out of the retention window. Returns the receipt id.
+    fn record_receipt(
+        env: &Env,
+        key: &SubscriptionKey,
+        token: &Address,
+        amount: i128,
+        fee: i128,
+        source: FundingSource,
+    ) -> u32 {
+        let id = Self::receipt_count(env, key);
+        let mut first = Self::first_receipt(env, key);
+        let receipt = Receipt {
+            id,
+            token: token.clone(),
+            amount,
+            fee,
+            timestamp: env.ledger().timestamp(),
+            source,
+            refunded: 0,
+        };
+        let receipt_key = DataKey::Receipt(key.clone(), id);
+        env.storage().persistent().set(&receipt_key, &receipt);
+        Self::extend_ttl(env, &receipt_key);
+
+        let count_key = DataKey::ReceiptCount(key.clone());
+        env.storage().persistent().set(&count_key, &(id + 1));
+        Self::extend_ttl(env, &count_key);
+
+        // Receipts that can still be disputed are kept past the retention
+        // count, up to the receipt cap. At most two are deleted per
+        // collection, which is enough to catch up once their windows close.
+        // An open dispute outlives its deleted receipt.
+        let now = env.ledger().timestamp();
+        let mut pruned = 0;
+        while pruned < 2 && id + 1 - first > RECEIPT_RETENTION {
+            let old_key = DataKey::Receipt(key.clone(), first);
+            let dispute_key = DataKey::Dispute(key.clone(), first);
+            let old: Option<Receipt> = env.storage().persistent().get(&old_key);
+            let disputable = old
+                .as_ref()
+                .is_some_and(|old| now <= old.timestamp + DISPUTE_WINDOW);
+            let disputed = env
+                .storage()
+                .persistent()
+                .get::<_, Dispute>(&dispute_key)
+                .is_some_and(|dispute| dispute.status == DisputeStatus::Open);
+            if (disputable || disputed) && id + 1 - first <= MAX_RECEIPTS {
+                break;
+            }
+
+            env.storage().persistent().remove(&old_key);
+            if !disputed {
+                env.storage().persistent().remove(&dispute_key);
+            }
+            if let Some(old) = old {
+                env.storage().persistent().remove(&DataKey::HoldOf(
+                    key.clone(),
+                    first,
+                    old.timestamp,
+                ));
+            }
+            first += 1;
+            pruned += 1;
+        }
+        if id + 1 > RECEIPT_RETENTION {
+            let start_key = DataKey::ReceiptStart(key.clone());
+            env.storage().persistent().set(&start_key, &first);
+            Self::extend_ttl(env, &start_key);
+        }
+        id
+    }
+
+    /// Id of the oldest receipt still stored for a subscription
+    fn first_receipt(env: &Env, key: &SubscriptionKey) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::ReceiptStart(key.clone()))
+            .unwrap_or_else(|| Self::receipt_count(env, key).saturating_sub(RECEIPT_RETENTION))
+    }
+
+    /// Whether a subscription's latest payment can still be disputed
+    fn in_dispute_window(env: &Env, key: &SubscriptionKey) -> bool {
+        let receipt_count = Self::receipt_count(env, key);
+        let now = env.ledger().timestamp();
+        receipt_count > 0
+            && env
+                .storage()
+                .persistent()
+                .get::<_, Receipt>(&DataKey::Receipt(key.clone(), receipt_count - 1))
+                .is_some_and(|receipt| now <= receipt.timestamp + DISPUTE_WINDOW)
+    }
+
+    /// Number of open disputes a merchant hasn't fully escrowed
+    fn load_unescrowed(env: &Env, merchant: &Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Unescrowed(merchant.clone()))
+            .unwrap_or(0)
+    }
+
+    fn save_unescrowed(env: &Env, merchant: &Address, count: u32) {
+        let key = DataKey::Unescrowed(merchant.clone());
+        if count == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &count);
+            Self::extend_ttl(env, &key);
+        }
+    }
+
+    /// Protocol fee charged on a merchant's collections in basis points
+    fn load_merchant_fee(env: &Env, merchant: &Address) -> u32 {
+        let key = DataKey::MerchantFee(merchant.clone());
+        match env.storage().persistent().get(&key) {
+            Some(fee_bps) => {
+                Self::extend_ttl(env, &key);
+                fee_bps
+            }
+            None => env
+                .storage()
+                .instance()
+                .get(&DataKey::ProtocolFee)
+                .unwrap_or(0),
+        }
+    }
+
+    /// Add a collected protocol fee to the running total for its token
+    fn record_protocol_fee(
+        env: &Env,
+        merchant: &Address,
+        token: &Address,
+        gross: i128,
+        fee: i128,
+        treasury: Address,
+    ) {
+        let key = DataKey::ProtocolFees(token.clone());
+        let total: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        env.storage().persistent().set(&key, &(total + fee));
+        Self::extend_ttl(env, &key);
+
+        ProtocolFeeCharged {
+            merchant: merchant.clone(),
+            token: token.clone(),
+            version: EVENT_VERSION,
+            gross,
+            fee,
+            net: gross - fee,
+            treasury,
+        }
+        .publish(env);
+    }
+
+    /// A merchant's revenue split (empty if paid directly)
+    fn load_revenue_split(env: &Env, merchant: &Address) -> Vec<Split> {
+        let key = DataKey::RevenueSplit(merchant.clone());
+        match env.storage().persistent().get(&key) {
+            Some(splits) => {
+                Self::extend_ttl(env, &key);
+                splits
+            }
+            None => Vec::new(env),
+        }
+    }
+
+    /// Pay `amount` held by the contract out to split recipients. Shares are
+    /// rounded down and the remainder goes to the first recipient.
+    fn pay_splits(env: &Env, splits: &Vec<Split>, token: &Address, amount: i128) {
+        let token_client = token::Client::new(env, token);
+        let contract = env.current_contract_address();
+
+        let share_of = |split: &Split| amount * split.share_bps as i128 / BPS_DENOMINATOR as i128;
+        let remainder = amount - splits.iter().map(|split| share_of(&split)).sum::<i128>();
+
+        for (i, split) in splits.iter().enumerate() {
+            let share = share_of(&split) + if i == 0 { remainder } else { 0 };
+            if share > 0 {
+                token_client.transfer(&contract, &split.recipient, &share);
+            }
+        }
+    }
+
+    /// Pay out merchant revenue held by the contract, through the merchant's
+    /// revenue split if it has one
+    fn pay_merchant(env: &Env, merchant: &Address, token: &Address, amount: i128) {
+        let splits = Self::load_revenue_split(env, merchant);
+        if splits.is_empty() {
+            token::Client::new(env, token).transfer(
+                &env.current_contract_address(),
+                merchant,
+                &amount,
+            );
+        } else {
+            Self::pay_splits(env, &splits, token, amount);
+        }
+    }
+
+    /// Settlement delay of a merchant (0 if paid out directly)
+    fn load_settlement_delay(env: &Env, merchant: &Address) -> u64 {
+        let key = DataKey::SettlementDelay(merchant.clone());
+        match env.storage().persistent().get(&key) {
+            Some(delay) => {
+                Self::extend_ttl(env, &key);
+                delay
+            }
+            None => 0,
+        }
+    }
+
+    /// Load a merchant's settlement state for a token
+    fn load_settlement(env: &Env, merchant: &Address, token: &Address) -> Settlement {
+        let key = DataKey::Settlement(merchant.clone(), token.clone());
+        match env.storage().persistent().get(&key) {
+            Some(settlement) => {
+                Self::extend_ttl(env, &key);
+                settlement
+            }
+            None => Settlement::default(),
+        }
+    }
+
+    /// Save a merchant's settlement state for a token
+    fn save_settlement(env: &Env, merchant: &Address, token: &Address, settlement: &Settlement) {
+        let key = DataKey::Settlement(merchant.clone(), token.clone());
+        env.storage().persistent().set(&key, settlement);
+        Self::extend_ttl(env, &key);
+    }
+
+    /// Queue a payment the contract received on the merchant's behalf
+    fn hold_payment(
+        env: &Env,
+        key: &SubscriptionKey,
+        token: &Address,
+        receipt_id: u32,
+        amount: i128,
+        delay: u64,
+    ) {
+        let mut settlement = Self::load_settlement(env, &key.merchant, token);
+        let id = settlement.next_hold;
+        let now = env.ledger().timestamp();
+        let hold = Hold {
+            subscriber: key.subscriber.clone(),
+            receipt_id,
+            collected_at: now,
+            amount,
+            release_at: now + delay,
+        };
+        let hold_key = DataKey::Hold(key.merchant.clone(), token.clone(), id);
+        env.storage().persistent().set(&hold_key, &hold);
+        Self::extend_ttl(env, &hold_key);
+
+        let receipt_hold_key = DataKey::HoldOf(key.clone(), receipt_id, now);
+        env.storage().persistent().set(&receipt_hold_key, &id);
+        Self::extend_ttl(env, &receipt_hold_key);
+
+        settlement.unsettled += amount;
+        settlement.next_hold += 1;
+        Self::save_settlement(env, &key.merchant, token, &settlement);
+    }
+
+    /// Take up to `amount` of a receipt's still-held funds out of settlement,
+    /// to be paid out of the contract instead. Returns the amount taken.
+    fn take_from_hold(env: &Env, key: &SubscriptionKey, receipt: &Receipt, amount: i128) -> i128 {
+        let token = &receipt.token;
+        let receipt_hold_key = DataKey::HoldOf(key.clone(), receipt.id, receipt.timestamp);
+        let Some(id) = env.storage().persistent().get::<_, u32>(&receipt_hold_key) else {
+            return 0;
+        };
+        let hold_key = DataKey::Hold(key.merchant.clone(), token.clone(), id);
+        let Some(mut hold) = env.storage().persistent().get::<_, Hold>(&hold_key) else {
+            return 0;
+        };
+
+        let taken = amount.min(hold.amount);
+        hold.amount -= taken;
+        env.storage().persistent().set(&hold_key, &hold);
+        Self::extend_ttl(env, &hold_key);
+
+        let mut settlement = Self::load_settlement(env, &key.merchant, token);
+        settlement.unsettled -= taken;
+        Self::save_settlement(env, &key.merchant, token, &settlement);
+        taken
+    }
+
+    /// Delete a subscription's retained receipts, their disputes and hold
+    /// links, and the receipt counter
+    ///
+    /// Holds themselves stay queued for the merchant; only the receipts'
+    /// links to them are dropped.
+    fn remove_receipts(env: &Env, key: &SubscriptionKey) {
+        let receipt_count = Self::receipt_count(env, key);
+        for id in Self::first_receipt(env, key)..receipt_count {
+            let receipt_key = DataKey::Receipt(key.clone(), id);
+            if let Some(receipt) = env.storage().persistent().get::<_, Receipt>(&receipt_key) {
+                env.storage().persistent().remove(&DataKey::HoldOf(
+                    key.clone(),
+                    id,
+                    receipt.timestamp,
+                ));
+            }
+            env.storage().persistent().remove(&receipt_key);
+            env.storage()
+                .persistent()
+                .remove(&DataKey::Dispute(key.clone(), id));
+        }
+        env.storage()
+            .persistent()
+            .remove(&DataKey::ReceiptCount(key.clone()));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::ReceiptStart(key.clone()));
+    }
+
+    /// Load a subscription, decoding older stored layouts
+    ///
+    /// Records from before v5 were paid for by their subscriber. Records
+    /// from before v4 only had an active flag: active ones become `Active`
+    /// and inactive ones `Cancelled`. Their start time wasn't recorded, so a
+    /// first payment is due straight away as before.
+    fn load_subscription(env: &Env, storage_key: &DataKey) -> Option<Subscription> {
+        let raw: Val = env.storage().persistent().get(storage_key)?;
+        Self::extend_ttl(env, storage_key);
+        if Self::is_current_layout(env, &raw, SUBSCRIPTION_FIELDS) {
+            return Some(Subscription::from_val(env, &raw));
+        }
+        if Self::is_current_layout(env, &raw, SUBSCRIPTION_V4_FIELDS) {
+            let legacy = SubscriptionV4::from_val(env, &raw);
+            return Some(Subscription {
+                payer: legacy.subscriber.clone(),
+                subscriber: legacy.subscriber,
+                merchant: legacy.merchant,
+                token: legacy.token,
+                amount: legacy.amount,
+                interval: legacy.interval,
+                starts_at: legacy.starts_at,
+                last_payment_time: legacy.last_payment_time,
+                status: legacy.status,
+                credit_score: legacy.credit_score,
+            });
+        }
+        if !Self::is_current_layout(env, &raw, SUBSCRIPTION_V1_FIELDS) {
+            panic!("Unknown subscription layout");
+        }
+
+        let legacy = SubscriptionV1::from_val(env, &raw);
+        Some(Subscription {
+            payer: legacy.subscriber.clone(),
+            subscriber: legacy.subscriber,
+            merchant: legacy.merchant,
+            token: legacy.token,
+            amount: legacy.amount,
+            interval: legacy.interval,
+            starts_at: 0,
+            last_payment_time: legacy.last_payment_time,
+            status: if legacy.is_active {
+                SubscriptionStatus::Active
+            } else {
+                SubscriptionStatus::Cancelled
+            },
+            credit_score: legacy.credit_score,
+        })
+    }
+
+    /// Load a user's debt record, converting a v1 record in place
+    ///
+    /// v1 only kept a running total, so the outstanding balance becomes a
+    /// single fee-free advance dated at conversion time. The merchant it
+    /// paid wasn't recorded; the contract's own address stands in for it.
+    fn load_user_debt(env: &Env, user: &Address) -> Option<UserDebt> {
+        let debt_key = DataKey::UserDebt(user.clone());
+        let raw: Val = env.storage().persistent().get(&debt_key)?;
+        if Self::is_current_layout(env, &raw, USER_DEBT_FIELDS) {
+            Self::extend_ttl(env, &debt_key);
+            return Some(UserDebt::from_val(env, &raw));
+        }
+
+        let legacy = UserDebtV1::from_val(env, &raw);
+        let mut user_debt = UserDebt {
+            amount: legacy.amount,
+            token: legacy.token,
+            advance_count: 0,
+            next_unpaid: 0,
+        };
+        if user_debt.amount > 0 {
+            let advance_key = DataKey::Advance(user.clone(), 0);
+            let advance = BnplAdvance {
+                id: 0,
+                merchant: env.current_contract_address(),
+                token: user_debt.token.clone(),
+                amount: user_debt.amount,
+                fee: 0,
+                timestamp: env.ledger().timestamp(),
+                repaid: 0,
+                written_off: false,
+                pool: Self::legacy_pool(env),
+            };
+            env.storage().persistent().set(&advance_key, &advance);
+            Self::extend_ttl(env, &advance_key);
+            user_debt.advance_count = 1;
+        }
+
+        env.storage().persistent().set(&debt_key, &user_debt);
+        Self::extend_ttl(env, &debt_key);
+
+        Some(user_debt)
+    }
+
+    /// Add a subscription to its subscriber's and merchant's indexes
+    /// (no-op if it is already listed)
+    fn index_subscription(env: &Env, key: &SubscriptionKey) {
+        let position_key = DataKey::IndexPosition(key.clone());
+        if env.storage().persistent().has(&position_key) {
+            return;
+        }
+
+        let position = IndexPosition {
+            subscriber_pos: Self::index_push(
+                env,
+                DataKey::SubscriberIndexLen(key.subscriber.clone()),
+                |pos| DataKey::SubscriberIndex(key.subscriber.clone(), pos),
+                &key.merchant,
+            ),
+            merchant_pos: Self::index_push(
+                env,
+                DataKey::MerchantIndexLen(key.merchant.clone()),
+                |pos| DataKey::MerchantIndex(key.merchant.clone(), pos),
+                &key.subscriber,
+            ),
+        };
+        env.storage().persistent().set(&position_key, &position);
+        Self::extend_ttl(env, &position_key);
+    }
+
+    /// Remove a subscription from both indexes (no-op if it isn't listed).
+    /// The last entry of each index moves into the freed slot.
+    fn unindex_subscription(env: &Env, key: &SubscriptionKey) {
+        let position_key = DataKey::IndexPosition(key.clone());
+        let position: IndexPosition = match env.storage().persistent().get(&position_key) {
+            Some(position) => position,
+            None => return,
+        };
+        env.storage().persistent().remove(&position_key);
+
+        if let Some(moved_merchant) = Self::index_swap_remove(
+            env,
+            DataKey::SubscriberIndexLen(key.subscriber.clone()),
+            |pos| DataKey::SubscriberIndex(key.subscriber.clone(), pos),
+            position.subscriber_pos,
+        ) {
+            let moved_key = DataKey::IndexPosition(SubscriptionKey {
+                subscriber: key.subscriber.clone(),
+                merchant: moved_merchant,
+            });
+            let mut moved: IndexPosition = env.storage().persistent().get(&moved_key).unwrap();
+            moved.subscriber_pos = position.subscriber_pos;
+            env.storage().persistent().set(&moved_key, &moved);
+            Self::extend_ttl(env, &moved_key);
+        }
+
+        if let Some(moved_subscriber) = Self::index_swap_remove(
+            env,
+            DataKey::MerchantIndexLen(key.merchant.clone()),
+            |pos| DataKey::MerchantIndex(key.merchant.clone(), pos),
+            position.merchant_pos,
+        ) {
+            let moved_key = DataKey::IndexPosition(SubscriptionKey {
+                subscriber: moved_subscriber,
+                merchant: key.merchant.clone(),
+            });
+            let mut moved: IndexPosition = env.storage().persistent().get(&moved_key).unwrap();
+            moved.merchant_pos = position.merchant_pos;
+            env.storage().persistent().set(&moved_key, &moved);
+            Self::extend_ttl(env, &moved_key);
+        }
+    }
+
+    /// Append an entry to an index, returning its position
+    fn index_push(
+        env: &Env,
+        len_key: DataKey,
+        slot_key: impl Fn(u32) -> DataKey,
+        entry: &Address,
+    ) -> u32 {
+        let len: u32 = env.storage().persistent().get(&len_key).unwrap_or(0);
+
+        let slot = slot_key(len);
+        env.storage().persistent().set(&slot, entry);
+        Self::extend_ttl(env, &slot);
+
+        env.storage().persistent().set(&len_key, &(len + 1));
+        Self::extend_ttl(env, &len_key);
+
+        len
+    }
+
+    /// Remove the entry at `pos` from an index by moving the last entry into
+    /// its slot. Returns the moved entry, if one had to move.
+    fn index_swap_remove(
+        env: &Env,
+        len_key: DataKey,
+        slot_key: impl Fn(u32) -> DataKey,
+        pos: u32,
+    ) -> Option<Address> {
+        let last = env.storage().persistent().get::<_, u32>(&len_key).unwrap() - 1;
+
+        let moved = if pos != last {
+            let entry: Address = env.storage().persistent().get(&slot_key(last)).unwrap();
+            env.storage().persistent().set(&slot_key(pos), &entry);
+            Self::extend_ttl(env, &slot_key(pos));
+            Some(entry)
+        } else {
+            None
+        };
+        env.storage().persistent().remove(&slot_key(last));
+
+        if last == 0 {
+            env.storage().persistent().remove(&len_key);
+        } else {
+            env.storage().persistent().set(&len_key, &last);
+            Self::extend_ttl(env, &len_key);
+        }
+
+        moved
+    }
+
+    /// Outstanding BNPL principal across all users owed to a vault for a token
+    fn total_debt(env: &Env, pool: &Address, token: &Address) -> i128 {
         env.storage()
+            .persistent()
+            .get(&DataKey::TotalDebt(pool.clone(), token.clone()))
+            .unwrap_or(0)
+    }
+
+    fn adjust_total_debt(env: &Env, pool: &Address, token: &Address, delta: i128) {
+        let key = DataKey::TotalDebt(pool.clone(), token.clone());
+        let total = Self::total_debt(env, pool, token) + delta;
+        env.storage().persistent().set(&key, &total);
+        Self::extend_ttl(env, &key);
+    }
+
+    /// Write off unpaid principal at the vault that lent it. Returns the
+    /// amount covered by the vault's bad-debt reserve.
+    fn write_off_with_pool(env: &Env, pool: &Address, token: &Address, principal: i128) -> i128 {
+        if principal == 0 {
+            return 0;
+        }
+        Self::adjust_total_debt(env, pool, token, -principal);
+        LiquidityPoolClient::new(env, pool).write_off(token, &principal)
+    }
+
+    /// Return a repayment to the vault that funded it: principal goes back
+    /// to LPs and fees are split with the bad-debt reserve. Debt carried over
+    /// from v1 was never tracked by the vault, so it is only transferred.
+    fn repay_to_pool(
+        env: &Env,
+        from: &Address,
+        token: &Address,
+        pool: &Address,
+        legacy: bool,
+        amount: i128,
+        principal: i128,
+    ) {
+        token::Client::new(env, token).transfer(from, pool, &amount);
+        if legacy {
+            return;
+        }
+
+        let fees = amount - principal;
+        let reserve_share_bps = Self::load_config(env).reserve_share_bps;
+        let reserve = fees * reserve_share_bps as i128 / BPS_DENOMINATOR as i128;
+        LiquidityPoolClient::new(env, pool).repay(token, &principal, &(fees - reserve), &reserve);
+        Self::adjust_total_debt(env, pool, token, -principal);
+    }
+
+    /// Pool owed the debt carried over from schema v1, pinned to the pool in
+    /// use when legacy debt is first converted or the storage is migrated
+    fn legacy_pool(env: &Env) -> Address {
+        if let Some(pool) = env.storage().instance().get(&DataKey::LegacyPool) {
+            return pool;
+        }
+        let pool: Address = env
+            .storage()
             .instance()
             .get(&DataKey::LiquidityPool)
-            .expect("Liquidity pool not initialized")
+            .expect("Liquidity pool not initialized");
+        env.storage().instance().set(&DataKey::LegacyPool, &pool);
+        pool
     }
 
-    /// Check if contract is initialized
-    pub fn is_initialized(env: Env) -> bool {
-        env.storage().instance().has(&DataKey::Initialized)
+    /// Whether an advance stands for debt carried over from schema v1
+    fn is_legacy_advance(env: &Env, advance: &BnplAdvance) -> bool {
+        advance.merchant == env.current_contract_address()
+    }
+
+    /// Share of pool funds lent out, in basis points
+    fn utilization_bps(outstanding_debt: i128, cash: i128) -> u32 {
+        let total = outstanding_debt + cash;
+        if total <= 0 {
+            return 0;
+        }
+        (outstanding_debt * BPS_DENOMINATOR as i128 / total) as u32
+    }
+
+    /// Panic unless the vault can fund an advance of `amount` without
+    /// running out of cash or exceeding the utilization limit
+    fn check_pool_capacity(
+        env: &Env,
+        config: &Config,
+        pool: &LiquidityPoolClient,
+        token: &Address,
+        amount: i128,
+    ) {
+        let reserves = pool.get_reserves(token);
+        if amount > reserves.cash {
+            panic!("Insufficient pool liquidity");
+        }
+
+        // outstanding / (outstanding + cash) must stay within the limit after the advance
+        let outstanding_debt = Self::total_debt(env, &pool.address, token) + amount;
+        let total = outstanding_debt + reserves.cash - amount;
+        if outstanding_debt * BPS_DENOMINATOR as i128 > config.max_utilization_bps as i128 * total {
+            panic!("Pool utilization limit exceeded");
+        }
+    }
+
+    /// Pay `amount` of a user's debt from `from`: allocate it to the oldest
+    /// outstanding advances, return it to the pool that funded them, update
+    /// the debt record and emit the repayment event
+    fn record_repayment(
+        env: &Env,
+        from: &Address,
+        user: &Address,
+        user_debt: &mut UserDebt,
+        amount: i128,
+    ) {
+        let debt_key = DataKey::UserDebt(user.clone());
+
+        // Allocate the repayment to the oldest outstanding advances first,
+        // principal before fee. Each run of advances funded by the same vault
+        // is returned to it in one transfer.
+        let mut remaining = amount;
+        let mut run: Option<(Address, bool)> = None;
+        let mut run_amount = 0;
+        let mut principal = 0;
+        while remaining > 0 && user_debt.next_unpaid < user_debt.advance_count {
+            let advance_key = DataKey::Advance(user.clone(), user_debt.next_unpaid);
+            let mut advance: BnplAdvance = env
+                .storage()
+                .persistent()
+                .get(&advance_key)
+                .expect("Advance not found");
+
+            let owed = advance.amount + advance.fee;
+            let applied = remaining.min(owed - advance.repaid);
+            let legacy = Self::is_legacy_advance(env, &advance);
+            let funded_by = (advance.pool.clone(), legacy);
+            if run.as_ref() != Some(&funded_by) {
+                if let Some((pool, legacy)) = run.replace(funded_by) {
+                    let token = &user_debt.token;
+                    Self::repay_to_pool(env, from, token, &pool, legacy, run_amount, principal);
+                }
+                run_amount = 0;
+                principal = 0;
+            }
+            run_amount += applied;
+            if !legacy {
+                principal += applied.min((advance.amount - advance.repaid).max(0));
+            }
+            advance.repaid += applied;
+            remaining -= applied;
+            if advance.repaid == owed {
+                user_debt.next_unpaid += 1;
+            }
+
+            env.storage().persistent().set(&advance_key, &advance);
+            Self::extend_ttl(env, &advance_key);
+        }
+
+        if let Some((pool, legacy)) = run {
+            let token = &user_debt.token;
+            Self::repay_to_pool(env, from, token, &pool, legacy, run_amount, principal);
+        }
+
+        // Update debt (the record is kept at zero so advance ids stay unique)
+        user_debt.amount -= amount;
+        env.storage().persistent().set(&debt_key, user_debt);
+        Self::extend_ttl(env, &debt_key);
+
+        // Log repayment
+        log!(
+            env,
+            "Debt repaid: {} tokens by {}, remaining: {}",
+            amount,
+            user,
+            user_debt.amount
+        );
+
+        // Emit repayment event
+        DebtRepaid {
+            user: user.clone(),
+            version: EVENT_VERSION,
+            token: user_debt.token.clone(),
+            amount,
+            remaining: user_debt.amount,
+        }
+        .publish(env);
+    }
+
+    /// Pull the user's configured auto-repay portion toward their debt after
+    /// a successful collection. Silently does nothing when auto-repay is off,
+    /// there is no debt in this token, or there is no surplus to draw from.
+    fn auto_repay_debt(env: &Env, user: &Address, token: &Address, surplus: i128) {
+        let portion_bps = Self::load_auto_repay(env, user);
+        if portion_bps == 0 {
+            return;
+        }
+
+        let mut user_debt = match Self::load_user_debt(env, user) {
+            Some(debt) => debt,
+            None => return,
+        };
+        if user_debt.token != *token {
+            return;
+        }
+
+        // Never pull more than the portion, the surplus or the remaining allowance.
+        // The portion rounds up so small balances still get cleared.
+        let contract_address = env.current_contract_address();
+        let token_client = token::Client::new(env, token);
+        let denominator = BPS_DENOMINATOR as i128;
+        let portion = (user_debt.amount * portion_bps as i128 + denominator - 1) / denominator;
+        let allowance = token_client.allowance(user, &contract_address);
+        let amount = portion.min(surplus).min(allowance);
+        if amount <= 0 {
+            return;
+        }
+
+        token_client.transfer_from(&contract_address, user, &contract_address, &amount);
+        Self::record_repayment(env, &contract_address, user, &mut user_debt, amount);
     }
 }
 
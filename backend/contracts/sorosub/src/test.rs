@@ -1,10 +1,15 @@
 #![cfg(test)]
 
 use super::*;
+use liquidity_pool::{LiquidityPoolContract, LiquidityPoolContractClient};
 use soroban_sdk::{
-    testutils::{Address as _, Ledger, LedgerInfo},
+    contract, contractimpl,
+    testutils::{
+        storage::{Instance as _, Persistent as _},
+        Address as _, Events, Ledger, LedgerInfo, MockAuth, MockAuthInvoke,
+    },
     token::{StellarAssetClient, TokenClient},
-    Address, Env,
+    vec, Address, Bytes, BytesN, Env, Event, IntoVal,
 };
 
 /// Helper to create a test token and mint to an address
@@ -46,8 +51,44 @@ fn advance_time(env: &Env, seconds: u64) {
     });
 }
 
+/// Helper to advance the ledger sequence (and time, at ~5 seconds per ledger)
+fn advance_ledgers(env: &Env, ledgers: u32) {
+    let current = env.ledger().timestamp();
+    let current_seq = env.ledger().sequence();
+    env.ledger().set(LedgerInfo {
+        timestamp: current + ledgers as u64 * 5,
+        protocol_version: 25,
+        sequence_number: current_seq + ledgers,
+        network_id: Default::default(),
+        base_reserve: 10,
+        min_temp_entry_ttl: 100,
+        min_persistent_entry_ttl: 100,
+        max_entry_ttl: 10000,
+    });
+}
+
+/// Helper to collect `periods` payments in a row, advancing `interval`
+/// seconds after each one (six monthly payments build a credit score of 60,
+/// enough for a BNPL advance on the next)
+fn collect_periods(
+    env: &Env,
+    client: &SoroSubContractClient,
+    subscriber: &Address,
+    merchant: &Address,
+    periods: u32,
+    interval: u64,
+) {
+    for _ in 0..periods {
+        client.collect_payment(subscriber, merchant);
+        advance_time(env, interval);
+    }
+}
+
 /// Helper to setup contract with initialization
-fn setup_contract<'a>(env: &'a Env, liquidity_pool: &Address) -> (Address, SoroSubContractClient<'a>) {
+fn setup_contract<'a>(
+    env: &'a Env,
+    liquidity_pool: &Address,
+) -> (Address, SoroSubContractClient<'a>) {
     let admin = Address::generate(env);
     let contract_id = env.register(SoroSubContract, ());
     let client = SoroSubContractClient::new(env, &contract_id);
@@ -55,6 +96,92 @@ fn setup_contract<'a>(env: &'a Env, liquidity_pool: &Address) -> (Address, SoroS
     (contract_id, client)
 }
 
+/// Helper to queue a parameter change as the admin and execute it once the
+/// timelock has passed
+fn apply_param_change(env: &Env, client: &SoroSubContractClient, change: ParamChange) {
+    let pending = client.queue_param_change(&client.get_admin(), &change);
+    advance_time(env, 48 * 60 * 60);
+    client.execute_param_change(&pending.id);
+}
+
+/// Helper to setup contract backed by a real liquidity pool vault
+fn setup_contract_with_pool<'a>(
+    env: &'a Env,
+) -> (
+    Address,
+    SoroSubContractClient<'a>,
+    LiquidityPoolContractClient<'a>,
+) {
+    let admin = Address::generate(env);
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(env, &contract_id);
+
+    let pool_id = env.register(LiquidityPoolContract, ());
+    let pool = LiquidityPoolContractClient::new(env, &pool_id);
+    pool.initialize(&admin, &contract_id);
+
+    client.initialize(&admin, &pool_id);
+    (contract_id, client, pool)
+}
+
+/// Stand-in for a deployment still running schema v1 code
+#[contract]
+pub struct SoroSubV1;
+
+#[contractimpl]
+impl SoroSubV1 {
+    pub fn initialize(env: Env, admin: Address, liquidity_pool: Address) {
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage()
+            .instance()
+            .set(&DataKey::LiquidityPool, &liquidity_pool);
+        env.storage().instance().set(&DataKey::Initialized, &true);
+    }
+
+    /// Store a subscription without listing it in any index
+    pub fn record_subscription(env: Env, subscription: SubscriptionV1) {
+        let key = SubscriptionKey {
+            subscriber: subscription.subscriber.clone(),
+            merchant: subscription.merchant.clone(),
+        };
+        env.storage()
+            .persistent()
+            .set(&DataKey::Subscription(key), &subscription);
+    }
+
+    /// Store a subscription in the v4 layout, from before gift subscriptions
+    pub fn record_subscription_v4(env: Env, subscription: SubscriptionV4) {
+        let key = SubscriptionKey {
+            subscriber: subscription.subscriber.clone(),
+            merchant: subscription.merchant.clone(),
+        };
+        env.storage()
+            .persistent()
+            .set(&DataKey::Subscription(key), &subscription);
+    }
+
+    /// Record BNPL debt the way v1 did: a bare running total
+    pub fn record_debt(env: Env, user: Address, token: Address, amount: i128) {
+        env.storage()
+            .persistent()
+            .set(&DataKey::UserDebt(user), &UserDebtV1 { amount, token });
+    }
+}
+
+/// Helper to swap the current contract code in at a v1 deployment's address
+fn upgrade_to_current<'a>(env: &'a Env, contract_id: &Address) -> SoroSubContractClient<'a> {
+    // Re-registering resets instance storage in the test host (an on-chain
+    // WASM update keeps it), so carry it across by hand
+    let instance = env.as_contract(contract_id, || env.storage().instance().all());
+    env.register_at(contract_id, SoroSubContract, ());
+    env.as_contract(contract_id, || {
+        for (key, value) in instance.iter() {
+            env.storage().instance().set(&key, &value);
+        }
+    });
+    SoroSubContractClient::new(env, contract_id)
+}
+
 // ============================================================================
 // BASIC SUBSCRIPTION TESTS
 // ============================================================================
@@ -82,13 +209,8 @@ fn test_create_subscription() {
     let amount: i128 = 100_0000000; // 100 tokens with 7 decimals
     let interval: u64 = 30 * 24 * 60 * 60; // 30 days in seconds
 
-    let subscription = client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    let subscription =
+        client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // Verify subscription data
     assert_eq!(subscription.subscriber, subscriber);
@@ -97,7 +219,7 @@ fn test_create_subscription() {
     assert_eq!(subscription.amount, amount);
     assert_eq!(subscription.interval, interval);
     assert_eq!(subscription.last_payment_time, 0);
-    assert!(subscription.is_active);
+    assert_eq!(subscription.status, SubscriptionStatus::Active);
     assert_eq!(subscription.credit_score, 0); // Starts at 0
 }
 
@@ -125,17 +247,16 @@ fn test_process_payment() {
 
     // Subscriber approves the SoroSub contract to spend tokens
     let expiration_ledger = env.ledger().sequence() + 1000;
-    token_client.approve(&subscriber, &contract_id, &(amount * 10), &expiration_ledger);
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
 
     // Create subscription
     let interval: u64 = 30 * 24 * 60 * 60; // 30 days
-    client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // Process first payment (should work since last_payment_time = 0)
     let paid = client.collect_payment(&subscriber, &merchant);
@@ -175,17 +296,16 @@ fn test_process_payment_too_early() {
 
     // Approve contract
     let expiration_ledger = env.ledger().sequence() + 1000;
-    token_client.approve(&subscriber, &contract_id, &(amount * 10), &expiration_ledger);
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
 
     // Create subscription with 30 day interval
     let interval: u64 = 30 * 24 * 60 * 60;
-    client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // First payment succeeds
     client.collect_payment(&subscriber, &merchant);
@@ -221,17 +341,16 @@ fn test_process_payment_after_interval() {
 
     // Approve contract
     let expiration_ledger = env.ledger().sequence() + 1000;
-    token_client.approve(&subscriber, &contract_id, &(amount * 10), &expiration_ledger);
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
 
     // Create subscription with 30 day interval
     let interval: u64 = 30 * 24 * 60 * 60;
-    client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // First payment
     client.collect_payment(&subscriber, &merchant);
@@ -267,25 +386,18 @@ fn test_cancel_subscription() {
     // Create subscription
     let amount: i128 = 100_0000000;
     let interval: u64 = 30 * 24 * 60 * 60;
-    client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // Cancel subscription
     client.cancel_subscription(&subscriber, &merchant);
 
     // Verify subscription is inactive
     let subscription = client.get_subscription(&subscriber, &merchant);
-    assert!(!subscription.is_active);
+    assert_eq!(subscription.status, SubscriptionStatus::Cancelled);
 }
 
 #[test]
-#[should_panic(expected = "Subscription is not active")]
-fn test_payment_after_cancel() {
+fn test_subscription_lifecycle_events() {
     let env = Env::default();
     env.mock_all_auths();
     setup_ledger(&env);
@@ -308,17 +420,93 @@ fn test_payment_after_cancel() {
 
     // Approve contract
     let expiration_ledger = env.ledger().sequence() + 1000;
-    token_client.approve(&subscriber, &contract_id, &(amount * 10), &expiration_ledger);
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
 
-    // Create and cancel subscription
+    // Each state transition emits a versioned, typed payload
     let interval: u64 = 30 * 24 * 60 * 60;
-    client.create_subscription(
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [SubscriptionCreated {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            token: token_address.clone(),
+            amount,
+            interval,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [PaymentCollected {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            token: token_address.clone(),
+            amount,
+            credit_score: 10,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+
+    client.cancel_subscription(&subscriber, &merchant);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [SubscriptionCancelled {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            token: token_address.clone(),
+            last_payment_time: env.ledger().timestamp(),
+            credit_score: 10,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #2)")]
+fn test_payment_after_cancel() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    // Create token and mint
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 10));
+
+    // Register and initialize contract
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(
         &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
     );
+
+    // Create and cancel subscription
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
     client.cancel_subscription(&subscriber, &merchant);
 
     // Payment should fail
@@ -349,17 +537,16 @@ fn test_can_process_payment() {
 
     // Approve contract
     let expiration_ledger = env.ledger().sequence() + 1000;
-    token_client.approve(&subscriber, &contract_id, &(amount * 10), &expiration_ledger);
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
 
     // Create subscription
     let interval: u64 = 30 * 24 * 60 * 60;
-    client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // Should be able to process (first payment, last_payment_time = 0)
     assert!(client.can_process_payment(&subscriber, &merchant));
@@ -409,17 +596,16 @@ fn test_credit_score_increment() {
 
     // Approve contract
     let expiration_ledger = env.ledger().sequence() + 5000;
-    token_client.approve(&subscriber, &contract_id, &(amount * 20), &expiration_ledger);
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
 
     // Create subscription
     let interval: u64 = 30 * 24 * 60 * 60;
-    client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // Verify initial credit score is 0
     assert_eq!(client.get_credit_score(&subscriber, &merchant), 0);
@@ -451,7 +637,7 @@ fn test_bnpl_trigger() {
     let subscriber = Address::generate(&env);
     let merchant = Address::generate(&env);
     let admin = Address::generate(&env);
-    let liquidity_pool = Address::generate(&env);
+    let lp = Address::generate(&env);
 
     // Create token
     let (token_client, stellar_client) = create_token(&env, &admin);
@@ -461,33 +647,28 @@ fn test_bnpl_trigger() {
 
     // Mint enough for initial payments to build credit
     stellar_client.mint(&subscriber, &(amount * 10));
-    // Mint to liquidity pool for BNPL
-    stellar_client.mint(&liquidity_pool, &(amount * 100));
+    // Mint to a liquidity provider funding the pool for BNPL
+    stellar_client.mint(&lp, &(amount * 100));
 
     // Register and initialize contract
-    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
 
     // Approve contract for subscriber
     let expiration_ledger = env.ledger().sequence() + 5000;
-    token_client.approve(&subscriber, &contract_id, &(amount * 20), &expiration_ledger);
-    // Approve contract for liquidity pool (needed for BNPL transfers)
-    token_client.approve(&liquidity_pool, &contract_id, &(amount * 100), &expiration_ledger);
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
 
     // Create subscription
     let interval: u64 = 30 * 24 * 60 * 60;
-    client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // Make 6 payments to build credit score to 60 (> 50 threshold)
-    for _ in 0..6 {
-        client.collect_payment(&subscriber, &merchant);
-        advance_time(&env, 31 * 24 * 60 * 60);
-    }
+    collect_periods(&env, &client, &subscriber, &merchant, 6, 31 * 24 * 60 * 60);
 
     // Verify credit score is now 60
     assert_eq!(client.get_credit_score(&subscriber, &merchant), 60);
@@ -507,15 +688,24 @@ fn test_bnpl_trigger() {
     assert_eq!(paid, amount);
 
     // Merchant should have received payment from liquidity pool
-    assert_eq!(token_client.balance(&merchant), merchant_balance_before + amount);
+    assert_eq!(
+        token_client.balance(&merchant),
+        merchant_balance_before + amount
+    );
 
-    // Subscriber should now have debt
-    let debt = client.get_user_debt(&subscriber).expect("Debt should exist");
-    assert_eq!(debt.amount, amount);
+    // Subscriber should now have debt, including the 1% BNPL fee
+    let debt = client
+        .get_user_debt(&subscriber)
+        .expect("Debt should exist");
+    assert_eq!(debt.amount, amount + amount / 100);
+
+    // The advance was drawn from vault reserves
+    let reserves = pool.get_reserves(&token_address);
+    assert_eq!(reserves.cash, amount * 99);
+    assert_eq!(reserves.borrowed, amount);
 }
 
 #[test]
-#[should_panic(expected = "Insufficient balance and credit score too low for BNPL")]
 fn test_bnpl_fails_low_credit() {
     let env = Env::default();
     env.mock_all_auths();
@@ -541,23 +731,19 @@ fn test_bnpl_fails_low_credit() {
 
     // Approve contract
     let expiration_ledger = env.ledger().sequence() + 5000;
-    token_client.approve(&subscriber, &contract_id, &(amount * 10), &expiration_ledger);
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
 
     // Create subscription
     let interval: u64 = 30 * 24 * 60 * 60;
-    client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // Make only 2 payments (credit score = 20, not enough for BNPL)
-    for _ in 0..2 {
-        client.collect_payment(&subscriber, &merchant);
-        advance_time(&env, 31 * 24 * 60 * 60);
-    }
+    collect_periods(&env, &client, &subscriber, &merchant, 2, 31 * 24 * 60 * 60);
 
     // Verify credit score is only 20
     assert_eq!(client.get_credit_score(&subscriber, &merchant), 20);
@@ -565,8 +751,15 @@ fn test_bnpl_fails_low_credit() {
     // Subscriber has no balance left
     assert_eq!(token_client.balance(&subscriber), 0);
 
-    // This should panic - insufficient balance AND low credit score
-    client.collect_payment(&subscriber, &merchant);
+    // Insufficient balance AND low credit score: nothing is paid and the
+    // subscription falls past due
+    assert_eq!(client.collect_payment(&subscriber, &merchant), 0);
+    assert_eq!(token_client.balance(&merchant), amount * 2);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::PastDue
+    );
+    assert!(client.get_user_debt(&subscriber).is_none());
 }
 
 #[test]
@@ -579,7 +772,7 @@ fn test_repay_debt() {
     let subscriber = Address::generate(&env);
     let merchant = Address::generate(&env);
     let admin = Address::generate(&env);
-    let liquidity_pool = Address::generate(&env);
+    let lp = Address::generate(&env);
 
     // Create token
     let (token_client, stellar_client) = create_token(&env, &admin);
@@ -589,32 +782,28 @@ fn test_repay_debt() {
 
     // Mint enough for initial payments
     stellar_client.mint(&subscriber, &(amount * 10));
-    // Mint to liquidity pool for BNPL
-    stellar_client.mint(&liquidity_pool, &(amount * 100));
+    // Mint to a liquidity provider funding the pool for BNPL
+    stellar_client.mint(&lp, &(amount * 100));
 
     // Register and initialize contract
-    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
 
     // Approve contract
     let expiration_ledger = env.ledger().sequence() + 5000;
-    token_client.approve(&subscriber, &contract_id, &(amount * 20), &expiration_ledger);
-    token_client.approve(&liquidity_pool, &contract_id, &(amount * 100), &expiration_ledger);
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
 
     // Create subscription and build credit score
     let interval: u64 = 30 * 24 * 60 * 60;
-    client.create_subscription(
-        &subscriber,
-        &merchant,
-        &token_address,
-        &amount,
-        &interval,
-    );
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
 
     // Make 6 payments to reach credit score 60
-    for _ in 0..6 {
-        client.collect_payment(&subscriber, &merchant);
-        advance_time(&env, 31 * 24 * 60 * 60);
-    }
+    collect_periods(&env, &client, &subscriber, &merchant, 6, 31 * 24 * 60 * 60);
 
     // Drain subscriber balance
     let remaining = token_client.balance(&subscriber);
@@ -624,76 +813,4856 @@ fn test_repay_debt() {
 
     // Trigger BNPL
     client.collect_payment(&subscriber, &merchant);
+    let owed = amount + amount / 100;
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [BnplAdvanced {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            token: token_address.clone(),
+            amount,
+            fee: amount / 100,
+            advance_id: 0,
+            total_debt: owed,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
 
-    // Verify debt was created
-    let debt = client.get_user_debt(&subscriber).expect("Debt should exist");
-    assert_eq!(debt.amount, amount);
+    // Verify debt was created (advance plus 1% BNPL fee)
+    let debt = client
+        .get_user_debt(&subscriber)
+        .expect("Debt should exist");
+    assert_eq!(debt.amount, owed);
 
     // Now give subscriber funds to repay
-    stellar_client.mint(&subscriber, &amount);
+    stellar_client.mint(&subscriber, &owed);
 
     // Repay half the debt
-    let remaining_debt = client.repay_debt(&subscriber, &(amount / 2));
-    assert_eq!(remaining_debt, amount / 2);
+    let remaining_debt = client.repay_debt(&subscriber, &(owed / 2));
+    assert_eq!(remaining_debt, owed / 2);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [DebtRepaid {
+            user: subscriber.clone(),
+            version: EVENT_VERSION,
+            token: token_address.clone(),
+            amount: owed / 2,
+            remaining: owed / 2,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
 
     // Verify partial repayment
-    let debt_after = client.get_user_debt(&subscriber).expect("Debt should exist");
-    assert_eq!(debt_after.amount, amount / 2);
+    let debt_after = client
+        .get_user_debt(&subscriber)
+        .expect("Debt should exist");
+    assert_eq!(debt_after.amount, owed / 2);
 
     // Repay rest of debt
-    let final_debt = client.repay_debt(&subscriber, &(amount / 2));
+    let final_debt = client.repay_debt(&subscriber, &(owed / 2));
     assert_eq!(final_debt, 0);
 
     // Debt should be cleared (returns None when debt is 0 and removed)
     assert!(client.get_user_debt(&subscriber).is_none());
-}
 
-// ============================================================================
-// INITIALIZATION TESTS
-// ============================================================================
+    // Principal returned to the vault; the fee is split 80/20 between LPs and the reserve
+    let fee = owed - amount;
+    let reserves = pool.get_reserves(&token_address);
+    assert_eq!(reserves.cash, amount * 100 + fee * 4 / 5);
+    assert_eq!(reserves.borrowed, 0);
+    assert_eq!(reserves.reserve, fee / 5);
+    assert_eq!(token_client.balance(&pool.address), amount * 100 + fee);
+}
 
 #[test]
-fn test_initialization() {
+fn test_bnpl_advance_ledger() {
     let env = Env::default();
     env.mock_all_auths();
     setup_ledger(&env);
 
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
     let admin = Address::generate(&env);
-    let liquidity_pool = Address::generate(&env);
+    let lp = Address::generate(&env);
 
-    // Register contract
-    let contract_id = env.register(SoroSubContract, ());
-    let client = SoroSubContractClient::new(&env, &contract_id);
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
 
-    // Should not be initialized yet
-    assert!(!client.is_initialized());
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
 
-    // Initialize
-    client.initialize(&admin, &liquidity_pool);
+    // Register and initialize contract
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
 
-    // Should be initialized now
-    assert!(client.is_initialized());
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
 
-    // Verify liquidity pool is set
-    assert_eq!(client.get_liquidity_pool(), liquidity_pool);
+    // Create subscription and build credit score to 60
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 6, 31 * 24 * 60 * 60);
+
+    // Three BNPL advances, one per billing period
+    let mut timestamps = [0u64; 3];
+    for timestamp in timestamps.iter_mut() {
+        *timestamp = env.ledger().timestamp();
+        client.collect_payment(&subscriber, &merchant);
+        advance_time(&env, 31 * 24 * 60 * 60);
+    }
+
+    // Each advance is owed with the 1% BNPL fee
+    let fee = amount / 100;
+    let owed = amount + fee;
+    let debt = client
+        .get_user_debt(&subscriber)
+        .expect("Debt should exist");
+    assert_eq!(debt.amount, owed * 3);
+    assert_eq!(debt.advance_count, 3);
+    assert_eq!(debt.next_unpaid, 0);
+
+    // Each advance is itemized
+    let advances = client.get_advances(&subscriber, &0, &10);
+    assert_eq!(advances.len(), 3);
+    for (i, advance) in advances.iter().enumerate() {
+        assert_eq!(advance.id, i as u32);
+        assert_eq!(advance.merchant, merchant);
+        assert_eq!(advance.token, token_address);
+        assert_eq!(advance.amount, amount);
+        assert_eq!(advance.fee, fee);
+        assert_eq!(advance.timestamp, timestamps[i]);
+        assert_eq!(advance.repaid, 0);
+        assert!(!advance.written_off);
+    }
+
+    // Pagination
+    let page = client.get_advances(&subscriber, &1, &1);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().id, 1);
+    assert_eq!(client.get_advances(&subscriber, &3, &10).len(), 0);
+
+    // Repay one and a half advances: allocated oldest-first
+    stellar_client.mint(&subscriber, &(owed * 3));
+    client.repay_debt(&subscriber, &(owed + amount / 2));
+
+    assert_eq!(client.get_advance(&subscriber, &0).repaid, owed);
+    assert_eq!(client.get_advance(&subscriber, &1).repaid, amount / 2);
+    assert_eq!(client.get_advance(&subscriber, &2).repaid, 0);
+    assert_eq!(client.get_user_debt(&subscriber).unwrap().next_unpaid, 1);
+
+    // Clearing the rest keeps the history
+    client.repay_debt(&subscriber, &(owed * 2 - amount / 2));
+    assert!(client.get_user_debt(&subscriber).is_none());
+    let advances = client.get_advances(&subscriber, &0, &10);
+    assert_eq!(advances.len(), 3);
+    assert!(advances.iter().all(|advance| advance.repaid == owed));
+
+    // A new advance continues the id sequence
+    client.collect_payment(&subscriber, &merchant);
+    let debt = client
+        .get_user_debt(&subscriber)
+        .expect("Debt should exist");
+    assert_eq!(debt.advance_count, 4);
+    assert_eq!(debt.next_unpaid, 3);
+    assert_eq!(client.get_advance(&subscriber, &3).amount, amount);
 }
 
 #[test]
-#[should_panic(expected = "Contract already initialized")]
-fn test_double_initialization() {
+#[should_panic(expected = "Pool utilization limit exceeded")]
+fn test_bnpl_refused_above_utilization_limit() {
     let env = Env::default();
     env.mock_all_auths();
     setup_ledger(&env);
 
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
     let admin = Address::generate(&env);
-    let liquidity_pool = Address::generate(&env);
+    let lp = Address::generate(&env);
 
-    let contract_id = env.register(SoroSubContract, ());
-    let client = SoroSubContractClient::new(&env, &contract_id);
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
 
-    // First initialization
-    client.initialize(&admin, &liquidity_pool);
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    // Pool holds only 120 tokens, so a 100 token advance is ~83% utilization
+    stellar_client.mint(&lp, &(amount * 6 / 5));
 
-    // Second initialization should fail
-    client.initialize(&admin, &liquidity_pool);
+    // Register and initialize contract backed by a small vault
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 6 / 5));
+
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    // Create subscription and build credit score to 60
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 6, 31 * 24 * 60 * 60);
+
+    // Credit is good enough, but the vault is too thin
+    client.collect_payment(&subscriber, &merchant);
+}
+
+#[test]
+fn test_pool_health() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 10));
+
+    // Register and initialize contract
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 10));
+
+    // Empty vault usage before any advance
+    let health = client.get_pool_health(&token_address);
+    assert_eq!(health.cash, amount * 10);
+    assert_eq!(health.outstanding_debt, 0);
+    assert_eq!(health.utilization_bps, 0);
+    assert_eq!(health.max_utilization_bps, 8_000);
+
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    // Create subscription, build credit and trigger one BNPL advance
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 7, 31 * 24 * 60 * 60);
+
+    // One tenth of the vault is lent out. The 1% fee is owed on top but
+    // isn't counted: utilization is principal only, like the vault's `borrowed`.
+    let owed = amount + amount / 100;
+    let health = client.get_pool_health(&token_address);
+    assert_eq!(health.cash, amount * 9);
+    assert_eq!(health.outstanding_debt, amount);
+    assert_eq!(health.utilization_bps, 1_000);
+    assert_eq!(pool.get_reserves(&token_address).borrowed, amount);
+
+    // Repaying brings utilization back down
+    stellar_client.mint(&subscriber, &owed);
+    client.repay_debt(&subscriber, &owed);
+    let health = client.get_pool_health(&token_address);
+    assert_eq!(health.cash, amount * 10 + (owed - amount) * 4 / 5);
+    assert_eq!(health.outstanding_debt, 0);
+    assert_eq!(health.utilization_bps, 0);
+}
+
+#[test]
+fn test_write_off_defaulted_debt() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    // Setup addresses
+    let defaulter = Address::generate(&env);
+    let payer = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    let fee = amount / 100;
+    stellar_client.mint(&defaulter, &(amount * 6));
+    stellar_client.mint(&payer, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    // Register and initialize contract
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    // Two subscribers build credit and each take one BNPL advance
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    let interval: u64 = 30 * 24 * 60 * 60;
+    for user in [&defaulter, &payer] {
+        token_client.approve(user, &contract_id, &(amount * 20), &expiration_ledger);
+        client.create_subscription(user, &merchant, &token_address, &amount, &interval);
+    }
+    for _ in 0..7 {
+        client.collect_payment(&defaulter, &merchant);
+        client.collect_payment(&payer, &merchant);
+        advance_time(&env, 31 * 24 * 60 * 60);
+    }
+
+    // One repays in full, feeding 20% of the fee into the bad-debt reserve
+    stellar_client.mint(&payer, &(amount + fee));
+    client.repay_debt(&payer, &(amount + fee));
+    assert_eq!(pool.get_reserves(&token_address).reserve, fee / 5);
+
+    // The other stops paying; after 90 days the debt is in default
+    advance_time(&env, 60 * 24 * 60 * 60);
+    let written_off = client.write_off_debt(&client.get_admin(), &defaulter);
+    assert_eq!(written_off, amount + fee);
+    assert!(client.get_user_debt(&defaulter).is_none());
+    assert!(client.get_advance(&defaulter, &0).written_off);
+
+    // Reserve absorbed what it could; LPs absorb the rest
+    let reserves = pool.get_reserves(&token_address);
+    assert_eq!(reserves.reserve, 0);
+    assert_eq!(reserves.borrowed, 0);
+    assert_eq!(reserves.cash, amount * 99 + fee * 4 / 5 + fee / 5);
+
+    // Pool accounting stays consistent with balances and SoroSub's debt total
+    assert_eq!(
+        token_client.balance(&pool.address),
+        reserves.cash + reserves.reserve
+    );
+    assert_eq!(client.get_pool_health(&token_address).outstanding_debt, 0);
+    assert_eq!(
+        pool.withdraw(&lp, &token_address, &(amount * 100)),
+        reserves.cash
+    );
+}
+
+#[test]
+#[should_panic(expected = "Debt is not in default")]
+fn test_write_off_before_default_period() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    // Register and initialize contract
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    // Build credit and take one advance
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 7, 31 * 24 * 60 * 60);
+
+    // Only 31 days have passed since the advance
+    client.write_off_debt(&client.get_admin(), &subscriber);
+}
+
+#[test]
+#[should_panic(expected = "BNPL unavailable after default")]
+fn test_no_bnpl_after_write_off() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    // Register and initialize contract
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    // Build credit and take one advance
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 7, 31 * 24 * 60 * 60);
+
+    // Default and write off
+    advance_time(&env, 60 * 24 * 60 * 60);
+    client.write_off_debt(&client.get_admin(), &subscriber);
+
+    // Credit score is still high, but the user can't borrow again
+    client.collect_payment(&subscriber, &merchant);
+}
+
+// ============================================================================
+// INITIALIZATION TESTS
+// ============================================================================
+
+#[test]
+fn test_initialization() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    // Register contract
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+
+    // Should not be initialized yet
+    assert!(!client.is_initialized());
+
+    // Initialize
+    client.initialize(&admin, &liquidity_pool);
+
+    // Should be initialized now
+    assert!(client.is_initialized());
+
+    // Verify liquidity pool is set
+    assert_eq!(client.get_liquidity_pool(), liquidity_pool);
+}
+
+#[test]
+#[should_panic(expected = "Contract already initialized")]
+fn test_double_initialization() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+
+    // First initialization
+    client.initialize(&admin, &liquidity_pool);
+
+    // Second initialization should fail
+    client.initialize(&admin, &liquidity_pool);
+}
+
+// ============================================================================
+// AUTO-REPAY TESTS
+// ============================================================================
+
+#[test]
+fn test_auto_repay_from_next_collection() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    // Register and initialize contract
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    // Create subscription and build credit score to 60
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 6, 31 * 24 * 60 * 60);
+
+    // Balance is exhausted, so this collection is fronted by the pool
+    client.collect_payment(&subscriber, &merchant);
+    let owed = amount + amount / 100;
+    assert_eq!(client.get_user_debt(&subscriber).unwrap().amount, owed);
+
+    // Opt in to full auto-repay and top up with more than one payment
+    client.set_auto_repay(&subscriber, &10_000);
+    assert_eq!(client.get_auto_repay(&subscriber), 10_000);
+    stellar_client.mint(&subscriber, &(amount * 3));
+    let pool_before = token_client.balance(&pool.address);
+
+    // Next collection pays the merchant and clears the debt from the surplus
+    advance_time(&env, 31 * 24 * 60 * 60);
+    client.collect_payment(&subscriber, &merchant);
+
+    assert!(client.get_user_debt(&subscriber).is_none());
+    assert_eq!(token_client.balance(&pool.address), pool_before + owed);
+    assert_eq!(token_client.balance(&subscriber), amount * 2 - owed);
+}
+
+#[test]
+fn test_auto_repay_limited_by_portion_and_surplus() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    // Register and initialize contract
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    // Create subscription, build credit and trigger two BNPL advances
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 8, 31 * 24 * 60 * 60);
+    let debt = (amount + amount / 100) * 2;
+    assert_eq!(client.get_user_debt(&subscriber).unwrap().amount, debt);
+
+    // Repay a quarter of the debt per collection
+    client.set_auto_repay(&subscriber, &2_500);
+    stellar_client.mint(&subscriber, &(amount * 2));
+
+    // Portion (50.5 tokens) is below the surplus (100 tokens)
+    let portion = debt / 4;
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(
+        client.get_user_debt(&subscriber).unwrap().amount,
+        debt - portion
+    );
+    assert_eq!(token_client.balance(&subscriber), amount - portion);
+
+    // With exactly one payment's worth of balance there is no surplus,
+    // so the debt stays untouched
+    stellar_client.mint(&subscriber, &portion);
+    advance_time(&env, 31 * 24 * 60 * 60);
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(
+        client.get_user_debt(&subscriber).unwrap().amount,
+        debt - portion
+    );
+    assert_eq!(token_client.balance(&subscriber), 0);
+
+    // Opting out stops automatic repayments
+    client.set_auto_repay(&subscriber, &0);
+    assert_eq!(client.get_auto_repay(&subscriber), 0);
+}
+
+#[test]
+fn test_auto_repay_clears_small_debt() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 100),
+        &expiration_ledger,
+    );
+    let interval: u64 = 3600;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 7, interval);
+    assert_eq!(client.get_user_debt(&subscriber).unwrap().amount, 101);
+
+    // A tenth of a few tokens rounds up, so the debt is eventually cleared
+    client.set_auto_repay(&subscriber, &1_000);
+    stellar_client.mint(&subscriber, &(amount * 60));
+    let mut collections = 0;
+    while client.get_user_debt(&subscriber).is_some() {
+        client.collect_payment(&subscriber, &merchant);
+        advance_time(&env, interval);
+        collections += 1;
+        assert!(collections < 40);
+    }
+    assert_eq!(pool.get_reserves(&token_address).borrowed, 0);
+}
+
+#[test]
+#[should_panic(expected = "Auto-repay portion exceeds 100%")]
+fn test_auto_repay_portion_too_large() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let subscriber = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.set_auto_repay(&subscriber, &10_001);
+}
+
+// ============================================================================
+// ADMIN TESTS
+// ============================================================================
+
+#[test]
+fn test_admin_rotates_liquidity_pool() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let new_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+    assert_eq!(client.get_admin(), admin);
+
+    let pending = client.queue_param_change(&admin, &ParamChange::LiquidityPool(new_pool.clone()));
+
+    // Only the stored admin authorized the rotation
+    let auths = env.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].0, admin);
+
+    // Nothing changes until the timelock has passed
+    assert_eq!(client.get_liquidity_pool(), liquidity_pool);
+    advance_time(&env, 48 * 60 * 60);
+    client.execute_param_change(&pending.id);
+
+    // Admin action is announced
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [LiquidityPoolUpdated {
+            version: EVENT_VERSION,
+            proposer: admin.clone(),
+            liquidity_pool: new_pool.clone(),
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_liquidity_pool(), new_pool);
+}
+
+#[test]
+fn test_admin_updates_config() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    // Defaults until the admin changes them
+    let mut config = client.get_config();
+    assert_eq!(config.credit_score_increment, 10);
+    assert_eq!(config.bnpl_min_credit_score, 50);
+    assert_eq!(config.max_utilization_bps, 8_000);
+
+    config.bnpl_min_credit_score = 30;
+    config.bnpl_fee_bps = 250;
+    let pending = client.queue_param_change(&admin, &ParamChange::Config(config.clone()));
+
+    let auths = env.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].0, admin);
+
+    advance_time(&env, 48 * 60 * 60);
+    client.execute_param_change(&pending.id);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [ConfigUpdated {
+            version: EVENT_VERSION,
+            proposer: admin.clone(),
+            config: config.clone(),
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_config(), config);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_queue_param_change_requires_admin() {
+    let env = Env::default();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let new_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    // Someone other than the admin signs the rotation on the admin's behalf
+    client
+        .mock_auths(&[MockAuth {
+            address: &attacker,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "queue_param_change",
+                args: (&admin, ParamChange::LiquidityPool(new_pool.clone())).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .queue_param_change(&admin, &ParamChange::LiquidityPool(new_pool.clone()));
+}
+
+#[test]
+#[should_panic(expected = "Basis-point value exceeds 100%")]
+fn test_update_config_rejects_invalid_bps() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    let mut config = client.get_config();
+    config.max_utilization_bps = 10_001;
+    client.queue_param_change(&client.get_admin(), &ParamChange::Config(config));
+}
+
+#[test]
+fn test_two_step_admin_transfer() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let new_admin = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    // Proposing does not hand over the role yet
+    let pending = client.propose_admin(&new_admin);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(pending.address, new_admin);
+    assert_eq!(
+        pending.expires_at,
+        env.ledger().timestamp() + 7 * 24 * 60 * 60
+    );
+    assert_eq!(client.get_pending_admin(), Some(pending));
+    assert_eq!(client.get_admin(), admin);
+
+    // The proposed admin accepts before expiry
+    advance_time(&env, 24 * 60 * 60);
+    client.accept_admin();
+    assert_eq!(env.auths()[0].0, new_admin);
+    assert_eq!(client.get_admin(), new_admin);
+    assert_eq!(client.get_pending_admin(), None);
+
+    // The new admin now governs the contract
+    client.queue_param_change(
+        &new_admin,
+        &ParamChange::LiquidityPool(liquidity_pool.clone()),
+    );
+    assert_eq!(env.auths()[0].0, new_admin);
+}
+
+#[test]
+#[should_panic(expected = "Admin proposal expired")]
+fn test_admin_proposal_expires() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let new_admin = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.propose_admin(&new_admin);
+
+    // Accepting after 7 days fails, so a mistyped address can't linger
+    advance_time(&env, 7 * 24 * 60 * 60 + 1);
+    client.accept_admin();
+}
+
+#[test]
+#[should_panic(expected = "No pending admin proposal")]
+fn test_cancel_admin_proposal() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let new_admin = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    client.propose_admin(&new_admin);
+    client.cancel_admin_proposal();
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(client.get_pending_admin(), None);
+    assert_eq!(client.get_admin(), admin);
+
+    // Nothing left to accept
+    client.accept_admin();
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_accept_admin_requires_proposed_address() {
+    let env = Env::default();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let new_admin = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    client
+        .mock_auths(&[MockAuth {
+            address: &admin,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "propose_admin",
+                args: (&new_admin,).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .propose_admin(&new_admin);
+
+    // Someone other than the proposed admin tries to accept
+    client
+        .mock_auths(&[MockAuth {
+            address: &attacker,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "accept_admin",
+                args: ().into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .accept_admin();
+}
+
+// ============================================================================
+// PAUSE TESTS
+// ============================================================================
+
+#[test]
+#[should_panic(expected = "New subscriptions are paused")]
+fn test_pause_blocks_new_subscriptions() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    client.set_pause(
+        &admin,
+        &PauseFlags {
+            subscriptions: true,
+            collections: false,
+            bnpl: false,
+        },
+    );
+
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &100_0000000,
+        &(30 * 24 * 60 * 60),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Collections are paused")]
+fn test_pause_blocks_collections() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 10));
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &amount,
+        &(30 * 24 * 60 * 60),
+    );
+
+    client.set_pause(
+        &admin,
+        &PauseFlags {
+            subscriptions: false,
+            collections: true,
+            bnpl: false,
+        },
+    );
+
+    client.collect_payment(&subscriber, &merchant);
+}
+
+#[test]
+#[should_panic(expected = "BNPL advances are paused")]
+fn test_pause_blocks_bnpl_only() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    // Register and initialize contract
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    // Pause BNPL only
+    client.set_pause(
+        &client.get_admin(),
+        &PauseFlags {
+            subscriptions: false,
+            collections: false,
+            bnpl: true,
+        },
+    );
+
+    // Normal collections keep working and build credit to 60
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 6, 31 * 24 * 60 * 60);
+    assert_eq!(client.get_credit_score(&subscriber, &merchant), 60);
+
+    // Balance is exhausted, and the pool may not front the payment
+    client.collect_payment(&subscriber, &merchant);
+}
+
+#[test]
+fn test_exits_available_while_paused() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    // Setup addresses
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    // Create token
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    // Register and initialize contract
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    // Approve contract
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    // Build credit and take one BNPL advance
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 7, 31 * 24 * 60 * 60);
+
+    // Everything is halted
+    let all_paused = PauseFlags {
+        subscriptions: true,
+        collections: true,
+        bnpl: true,
+    };
+    client.set_pause(&client.get_admin(), &all_paused);
+    assert_eq!(client.get_pause(), all_paused);
+
+    // Users can still repay and walk away
+    let owed = client.get_user_debt(&subscriber).unwrap().amount;
+    stellar_client.mint(&subscriber, &owed);
+    assert_eq!(client.repay_debt(&subscriber, &owed), 0);
+    client.cancel_subscription(&subscriber, &merchant);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::Cancelled
+    );
+}
+
+#[test]
+fn test_pauser_can_pause() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let pauser = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.grant_role(&Role::Pauser, &pauser);
+    assert!(client.has_role(&Role::Pauser, &pauser));
+
+    let flags = PauseFlags {
+        subscriptions: false,
+        collections: true,
+        bnpl: true,
+    };
+    client.set_pause(&pauser, &flags);
+    assert_eq!(env.auths()[0].0, pauser);
+    assert_eq!(client.get_pause(), flags);
+
+    // Admin lifts the pause
+    let admin = client.get_admin();
+    let running = PauseFlags {
+        subscriptions: false,
+        collections: false,
+        bnpl: false,
+    };
+    client.set_pause(&admin, &running);
+    assert_eq!(client.get_pause(), running);
+}
+
+#[test]
+#[should_panic(expected = "Pauser can only pause")]
+fn test_pauser_cannot_unpause() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let pauser = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+    client.grant_role(&Role::Pauser, &pauser);
+
+    client.set_pause(
+        &pauser,
+        &PauseFlags {
+            subscriptions: true,
+            collections: true,
+            bnpl: true,
+        },
+    );
+    client.set_pause(
+        &pauser,
+        &PauseFlags {
+            subscriptions: true,
+            collections: false,
+            bnpl: true,
+        },
+    );
+}
+
+#[test]
+#[should_panic(expected = "Caller does not have the required role")]
+fn test_set_pause_rejects_others() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let stranger = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.set_pause(
+        &stranger,
+        &PauseFlags {
+            subscriptions: true,
+            collections: true,
+            bnpl: true,
+        },
+    );
+}
+
+// ============================================================================
+// UPGRADE / MIGRATION TESTS
+// ============================================================================
+
+#[test]
+fn test_fresh_deploy_uses_current_schema() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    assert_eq!(client.get_schema_version(), SCHEMA_VERSION);
+}
+
+#[test]
+fn test_upgrade_converts_legacy_debt_lazily() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+
+    // v1 deployment with an outstanding BNPL balance
+    let contract_id = env.register(SoroSubV1, ());
+    let v1 = SoroSubV1Client::new(&env, &contract_id);
+    v1.initialize(&admin, &liquidity_pool);
+    v1.record_debt(&user, &token, &150);
+
+    let client = upgrade_to_current(&env, &contract_id);
+    assert_eq!(client.get_schema_version(), 1);
+    assert_eq!(client.get_admin(), admin);
+
+    // The v1 record reads back in the current layout, as one fee-free advance
+    let debt = client.get_user_debt(&user).unwrap();
+    assert_eq!(debt.amount, 150);
+    assert_eq!(debt.token, token);
+    assert_eq!(debt.advance_count, 1);
+    assert_eq!(debt.next_unpaid, 0);
+
+    let advance = client.get_advance(&user, &0);
+    assert_eq!(advance.amount, 150);
+    assert_eq!(advance.fee, 0);
+    assert_eq!(advance.repaid, 0);
+
+    // Converted exactly once, and owed to the v1 pool rather than the vault
+    client.get_user_debt(&user);
+    assert_eq!(client.get_user_debt(&user).unwrap().advance_count, 1);
+    env.as_contract(&contract_id, || {
+        assert!(!env
+            .storage()
+            .persistent()
+            .has(&DataKey::TotalDebt(liquidity_pool.clone(), token.clone())));
+        let legacy_pool: Address = env.storage().instance().get(&DataKey::LegacyPool).unwrap();
+        assert_eq!(legacy_pool, liquidity_pool);
+    });
+}
+
+/// Upload the smallest module the host accepts as contract code (just the
+/// env meta section), standing in for a real build of this contract
+fn upload_stub_wasm(env: &Env) -> BytesN<32> {
+    let mut wasm = Bytes::from_slice(env, b"\0asm\x01\0\0\0\0\x1e\x11contractenvmetav0");
+    wasm.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 25, 0, 0, 0, 0]);
+    env.deployer().upload_contract_wasm(wasm)
+}
+
+#[test]
+fn test_upgrade_then_repay_legacy_debt() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+
+    // A v1 deployment whose pool predates the vault: the contract's storage
+    // still holds a bare debt total owed to it
+    let legacy_pool = Address::generate(&env);
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage()
+            .instance()
+            .set(&DataKey::LiquidityPool, &legacy_pool);
+        env.storage().instance().set(&DataKey::Initialized, &true);
+        env.storage().persistent().set(
+            &DataKey::UserDebt(user.clone()),
+            &UserDebtV1 {
+                amount: 150,
+                token: token.clone(),
+            },
+        );
+    });
+    assert_eq!(client.get_schema_version(), 1);
+
+    client.upgrade(&upload_stub_wasm(&env));
+    assert_eq!(env.auths()[0].0, admin);
+
+    // The host now runs the stub, so put the native build back in its place
+    let client = upgrade_to_current(&env, &contract_id);
+    assert_eq!(client.migrate(&vec![&env, user.clone()], &vec![&env]), 1);
+    assert_eq!(client.get_schema_version(), SCHEMA_VERSION);
+
+    // Repayments of the legacy debt go straight to the pool that funded it
+    stellar_client.mint(&user, &150);
+    assert_eq!(client.repay_debt(&user, &100), 50);
+    assert_eq!(token_client.balance(&legacy_pool), 100);
+
+    // Even once a vault has been rotated in, which never lent it anything
+    let pool_id = env.register(LiquidityPoolContract, ());
+    let pool = LiquidityPoolContractClient::new(&env, &pool_id);
+    pool.initialize(&admin, &contract_id);
+    stellar_client.mint(&lp, &1000);
+    pool.deposit(&lp, &token, &1000);
+    let pending = client.queue_param_change(&admin, &ParamChange::LiquidityPool(pool_id.clone()));
+    advance_time(&env, 48 * 60 * 60);
+    client.execute_param_change(&pending.id);
+
+    assert_eq!(client.repay_debt(&user, &50), 0);
+    assert_eq!(token_client.balance(&legacy_pool), 150);
+    assert_eq!(token_client.balance(&pool_id), 1000);
+    assert_eq!(pool.get_reserves(&token).borrowed, 0);
+    assert!(client.get_user_debt(&user).is_none());
+}
+
+#[test]
+fn test_migrate_rewrites_legacy_records() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let user_a = Address::generate(&env);
+    let user_b = Address::generate(&env);
+    let user_c = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+
+    let contract_id = env.register(SoroSubV1, ());
+    let v1 = SoroSubV1Client::new(&env, &contract_id);
+    v1.initialize(&admin, &liquidity_pool);
+    v1.record_debt(&user_a, &token, &100);
+    v1.record_debt(&user_b, &token, &200);
+
+    let client = upgrade_to_current(&env, &contract_id);
+
+    // user_c has no debt and the subscription doesn't exist: both are skipped
+    let migrated = client.migrate(
+        &vec![&env, user_a.clone(), user_b.clone(), user_c.clone()],
+        &vec![
+            &env,
+            SubscriptionKey {
+                subscriber: user_a.clone(),
+                merchant: merchant.clone(),
+            },
+        ],
+    );
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(migrated, 2);
+    assert_eq!(client.get_schema_version(), SCHEMA_VERSION);
+
+    // Records are stored in the current layout now
+    env.as_contract(&contract_id, || {
+        let debt: UserDebt = env
+            .storage()
+            .persistent()
+            .get(&DataKey::UserDebt(user_b.clone()))
+            .unwrap();
+        assert_eq!(debt.amount, 200);
+        assert_eq!(debt.advance_count, 1);
+    });
+
+    // Running it again has nothing left to convert
+    assert_eq!(client.migrate(&vec![&env, user_a, user_b], &vec![&env]), 0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_upgrade_requires_admin() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let stranger = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    let new_wasm_hash = BytesN::from_array(&env, &[7; 32]);
+    client
+        .mock_auths(&[MockAuth {
+            address: &stranger,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "upgrade",
+                args: (new_wasm_hash.clone(),).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .upgrade(&new_wasm_hash);
+}
+
+// ============================================================================
+// ROLE TESTS
+// ============================================================================
+
+#[test]
+fn test_grant_and_revoke_role() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let risk_manager = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    // The admin holds every role implicitly
+    assert!(client.has_role(&Role::Admin, &admin));
+    assert!(client.has_role(&Role::RiskManager, &admin));
+    assert!(!client.has_role(&Role::RiskManager, &risk_manager));
+
+    client.grant_role(&Role::RiskManager, &risk_manager);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [RoleGranted {
+            version: EVENT_VERSION,
+            admin: admin.clone(),
+            role: Role::RiskManager,
+            account: risk_manager.clone(),
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert!(client.has_role(&Role::RiskManager, &risk_manager));
+    assert!(!client.has_role(&Role::Treasurer, &risk_manager));
+
+    // Roles are scoped: a risk manager can tune risk parameters...
+    let mut config = client.get_config();
+    config.bnpl_min_credit_score = 70;
+    let pending = client.queue_param_change(&risk_manager, &ParamChange::Config(config.clone()));
+    assert_eq!(env.auths()[0].0, risk_manager);
+    assert_eq!(pending.proposer, risk_manager);
+
+    client.revoke_role(&Role::RiskManager, &risk_manager);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [RoleRevoked {
+            version: EVENT_VERSION,
+            admin: admin.clone(),
+            role: Role::RiskManager,
+            account: risk_manager.clone(),
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert!(!client.has_role(&Role::RiskManager, &risk_manager));
+}
+
+#[test]
+#[should_panic(expected = "Caller does not have the required role")]
+fn test_role_is_scoped_to_its_actions() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let risk_manager = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let new_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    // ...but rotating the liquidity pool needs the treasurer role
+    client.grant_role(&Role::RiskManager, &risk_manager);
+    client.queue_param_change(&risk_manager, &ParamChange::LiquidityPool(new_pool));
+}
+
+#[test]
+#[should_panic(expected = "Caller does not have the required role")]
+fn test_revoked_role_loses_access() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let treasurer = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let new_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.grant_role(&Role::Treasurer, &treasurer);
+    client.queue_param_change(&treasurer, &ParamChange::LiquidityPool(new_pool));
+
+    client.revoke_role(&Role::Treasurer, &treasurer);
+    client.queue_param_change(&treasurer, &ParamChange::LiquidityPool(liquidity_pool));
+}
+
+#[test]
+#[should_panic(expected = "Admin role is transferred with propose_admin")]
+fn test_admin_role_cannot_be_granted() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let other = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.grant_role(&Role::Admin, &other);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_grant_role_requires_admin() {
+    let env = Env::default();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    // Attackers cannot hand themselves a role
+    client
+        .mock_auths(&[MockAuth {
+            address: &attacker,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "grant_role",
+                args: (Role::Pauser, &attacker).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .grant_role(&Role::Pauser, &attacker);
+}
+
+#[test]
+fn test_merchant_verifier() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let verifier = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.grant_role(&Role::MerchantVerifier, &verifier);
+    assert!(!client.is_merchant_verified(&merchant));
+
+    client.set_merchant_verified(&verifier, &merchant, &true);
+    assert_eq!(env.auths()[0].0, verifier);
+    assert!(client.is_merchant_verified(&merchant));
+
+    client.set_merchant_verified(&verifier, &merchant, &false);
+    assert!(!client.is_merchant_verified(&merchant));
+}
+
+// ============================================================================
+// TIMELOCK TESTS
+// ============================================================================
+
+#[test]
+fn test_param_change_timelock() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+
+    let contract_id = env.register(SoroSubContract, ());
+    let client = SoroSubContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &liquidity_pool);
+
+    let mut config = client.get_config();
+    config.bnpl_min_credit_score = 80;
+    let change = ParamChange::Config(config.clone());
+    let pending = client.queue_param_change(&admin, &change);
+
+    // The queued change is visible and announced
+    assert_eq!(pending.id, 0);
+    assert_eq!(pending.change, change);
+    assert_eq!(pending.proposer, admin);
+    assert_eq!(
+        pending.execute_after,
+        env.ledger().timestamp() + 48 * 60 * 60
+    );
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [ParamChangeQueued {
+            version: EVENT_VERSION,
+            caller: admin.clone(),
+            id: 0,
+            change: change.clone(),
+            execute_after: pending.execute_after,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_param_change(&0), Some(pending.clone()));
+
+    // Once the delay has passed, anyone can execute it without signing
+    advance_time(&env, 48 * 60 * 60);
+    client.execute_param_change(&0);
+    assert_eq!(env.auths().len(), 0);
+    assert_eq!(client.get_config(), config);
+    assert_eq!(client.get_param_change(&0), None);
+}
+
+#[test]
+#[should_panic(expected = "Parameter change is still timelocked")]
+fn test_param_change_not_executable_early() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let liquidity_pool = Address::generate(&env);
+    let new_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    let pending =
+        client.queue_param_change(&client.get_admin(), &ParamChange::LiquidityPool(new_pool));
+
+    advance_time(&env, 48 * 60 * 60 - 1);
+    client.execute_param_change(&pending.id);
+}
+
+#[test]
+#[should_panic(expected = "Parameter change not found")]
+fn test_cancelled_param_change_cannot_execute() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let treasurer = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let new_pool = Address::generate(&env);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    client.grant_role(&Role::Treasurer, &treasurer);
+
+    let pending = client.queue_param_change(&treasurer, &ParamChange::LiquidityPool(new_pool));
+
+    // Cancelled during the delay
+    advance_time(&env, 24 * 60 * 60);
+    client.cancel_param_change(&treasurer, &pending.id);
+    assert_eq!(env.auths()[0].0, treasurer);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [ParamChangeCancelled {
+            version: EVENT_VERSION,
+            caller: treasurer.clone(),
+            id: pending.id,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_param_change(&pending.id), None);
+
+    advance_time(&env, 24 * 60 * 60);
+    client.execute_param_change(&pending.id);
+}
+
+#[test]
+fn test_fee_and_settlement_changes_are_timelocked() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let treasurer = Address::generate(&env);
+    let verifier = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+    client.grant_role(&Role::Treasurer, &treasurer);
+    client.grant_role(&Role::MerchantVerifier, &verifier);
+
+    client.queue_param_change(&contract_admin, &ParamChange::Treasury(treasury.clone()));
+    client.queue_param_change(
+        &treasurer,
+        &ParamChange::MerchantFee(merchant.clone(), Some(500)),
+    );
+    client.queue_param_change(
+        &verifier,
+        &ParamChange::SettlementDelay(merchant.clone(), 3600),
+    );
+    client.queue_param_change(&treasurer, &ParamChange::ProtocolFee(100));
+    client.cancel_param_change(&treasurer, &3);
+
+    // Nothing changes while the changes are pending, and they can be listed
+    assert_eq!(client.get_treasury(), None);
+    assert_eq!(client.get_merchant_fee(&merchant), 0);
+    assert_eq!(client.get_settlement_delay(&merchant), 0);
+    let pending = client.list_param_changes(&0, &10);
+    assert_eq!(pending.len(), 3);
+    assert_eq!(pending.get(0).unwrap().proposer, contract_admin);
+    assert_eq!(pending.get(2).unwrap().proposer, verifier);
+    assert_eq!(client.list_param_changes(&1, &1).len(), 1);
+
+    advance_time(&env, 48 * 60 * 60);
+    for id in 0..3 {
+        client.execute_param_change(&id);
+    }
+    assert_eq!(client.get_treasury(), Some(treasury));
+    assert_eq!(client.get_merchant_fee(&merchant), 500);
+    assert_eq!(client.get_settlement_delay(&merchant), 3600);
+    assert_eq!(client.list_param_changes(&0, &10).len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Caller does not have the required role")]
+fn test_treasury_change_requires_admin() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let treasurer = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+    client.grant_role(&Role::Treasurer, &treasurer);
+
+    client.queue_param_change(&treasurer, &ParamChange::Treasury(treasurer.clone()));
+}
+
+#[test]
+fn test_pool_rotation_keeps_debt_owed_to_funding_pool() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 200));
+
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    // A second vault to rotate to
+    let new_pool_id = env.register(LiquidityPoolContract, ());
+    let new_pool = LiquidityPoolContractClient::new(&env, &new_pool_id);
+    new_pool.initialize(&admin, &contract_id);
+    new_pool.deposit(&lp, &token_address, &(amount * 100));
+
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 7, 31 * 24 * 60 * 60);
+    assert_eq!(client.get_advance(&subscriber, &0).pool, pool.address);
+
+    // The pool rotates while that advance is still outstanding
+    let pending = client.queue_param_change(
+        &client.get_admin(),
+        &ParamChange::LiquidityPool(new_pool_id.clone()),
+    );
+    advance_time(&env, 48 * 60 * 60);
+    client.execute_param_change(&pending.id);
+    assert_eq!(client.get_liquidity_pool(), new_pool_id);
+
+    // The next advance comes from the new vault, which only counts its own
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(client.get_advance(&subscriber, &1).pool, new_pool_id);
+    assert_eq!(pool.get_reserves(&token_address).borrowed, amount);
+    assert_eq!(new_pool.get_reserves(&token_address).borrowed, amount);
+    assert_eq!(
+        client.get_pool_health(&token_address).outstanding_debt,
+        amount
+    );
+
+    // One repayment covering both is split between the vaults that funded them
+    let owed = amount + amount / 100;
+    let pool_cash = pool.get_reserves(&token_address).cash;
+    let new_pool_cash = new_pool.get_reserves(&token_address).cash;
+    stellar_client.mint(&subscriber, &(owed * 2));
+    assert_eq!(client.repay_debt(&subscriber, &(owed * 2)), 0);
+    assert_eq!(pool.get_reserves(&token_address).borrowed, 0);
+    assert_eq!(new_pool.get_reserves(&token_address).borrowed, 0);
+    let lp_fee = (owed - amount) * 4 / 5;
+    assert_eq!(
+        pool.get_reserves(&token_address).cash,
+        pool_cash + amount + lp_fee
+    );
+    assert_eq!(
+        new_pool.get_reserves(&token_address).cash,
+        new_pool_cash + amount + lp_fee
+    );
+    assert_eq!(client.get_pool_health(&token_address).outstanding_debt, 0);
+}
+
+#[test]
+fn test_write_off_after_pool_rotation() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 200));
+
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+    let new_pool_id = env.register(LiquidityPoolContract, ());
+    let new_pool = LiquidityPoolContractClient::new(&env, &new_pool_id);
+    new_pool.initialize(&admin, &contract_id);
+    new_pool.deposit(&lp, &token_address, &(amount * 100));
+
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+    collect_periods(&env, &client, &subscriber, &merchant, 7, 31 * 24 * 60 * 60);
+
+    // One advance from each vault, then the subscriber stops paying
+    let pending = client.queue_param_change(
+        &client.get_admin(),
+        &ParamChange::LiquidityPool(new_pool_id.clone()),
+    );
+    advance_time(&env, 48 * 60 * 60);
+    client.execute_param_change(&pending.id);
+    client.collect_payment(&subscriber, &merchant);
+    advance_time(&env, 90 * 24 * 60 * 60);
+
+    // Each vault absorbs the principal it lent
+    let owed = amount + amount / 100;
+    assert_eq!(
+        client.write_off_debt(&client.get_admin(), &subscriber),
+        owed * 2
+    );
+    assert_eq!(pool.get_reserves(&token_address).borrowed, 0);
+    assert_eq!(new_pool.get_reserves(&token_address).borrowed, 0);
+    assert_eq!(pool.get_reserves(&token_address).cash, amount * 99);
+    assert_eq!(new_pool.get_reserves(&token_address).cash, amount * 99);
+}
+
+// ============================================================================
+// INDEX TESTS
+// ============================================================================
+
+#[test]
+fn test_subscription_indexes() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let merchant_a = Address::generate(&env);
+    let merchant_b = Address::generate(&env);
+    let merchant_c = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&alice, &merchant_a, &token, &100, &interval);
+    client.create_subscription(&alice, &merchant_b, &token, &200, &interval);
+    client.create_subscription(&alice, &merchant_c, &token, &300, &interval);
+    client.create_subscription(&bob, &merchant_a, &token, &400, &interval);
+
+    // Subscriber view, in creation order
+    let page = client.list_subscriptions_by_subscriber(&alice, &0, &10);
+    assert_eq!(page.len(), 3);
+    assert_eq!(page.get(0).unwrap().merchant, merchant_a);
+    assert_eq!(page.get(2).unwrap().amount, 300);
+
+    // Pagination
+    let page = client.list_subscriptions_by_subscriber(&alice, &1, &1);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().merchant, merchant_b);
+    assert_eq!(
+        client
+            .list_subscriptions_by_subscriber(&alice, &3, &10)
+            .len(),
+        0
+    );
+
+    // Merchant view
+    let page = client.list_subscribers_by_merchant(&merchant_a, &0, &10);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(0).unwrap().subscriber, alice);
+    assert_eq!(page.get(1).unwrap().subscriber, bob);
+
+    // Cancelling drops the subscription from both indexes; the last entry
+    // takes its slot
+    client.cancel_subscription(&alice, &merchant_a);
+    let page = client.list_subscriptions_by_subscriber(&alice, &0, &10);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(0).unwrap().merchant, merchant_c);
+    assert_eq!(page.get(1).unwrap().merchant, merchant_b);
+    let page = client.list_subscribers_by_merchant(&merchant_a, &0, &10);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().subscriber, bob);
+
+    // The moved entry can still be removed cleanly
+    client.cancel_subscription(&alice, &merchant_c);
+    let page = client.list_subscriptions_by_subscriber(&alice, &0, &10);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().merchant, merchant_b);
+
+    // Resubscribing lists it again, once
+    client.create_subscription(&alice, &merchant_a, &token, &100, &interval);
+    client.cancel_subscription(&bob, &merchant_a);
+    let page = client.list_subscribers_by_merchant(&merchant_a, &0, &10);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get(0).unwrap().subscriber, alice);
+    assert_eq!(
+        client.list_subscriptions_by_subscriber(&bob, &0, &10).len(),
+        0
+    );
+}
+
+#[test]
+fn test_migrate_indexes_existing_subscriptions() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+
+    // A subscription created before indexes existed
+    let contract_id = env.register(SoroSubV1, ());
+    let v1 = SoroSubV1Client::new(&env, &contract_id);
+    v1.initialize(&admin, &liquidity_pool);
+    v1.record_subscription(&SubscriptionV1 {
+        subscriber: subscriber.clone(),
+        merchant: merchant.clone(),
+        token: token_client.address.clone(),
+        amount: 100,
+        interval: 30 * 24 * 60 * 60,
+        last_payment_time: 0,
+        is_active: true,
+        credit_score: 0,
+    });
+
+    let client = upgrade_to_current(&env, &contract_id);
+    assert_eq!(
+        client
+            .list_subscriptions_by_subscriber(&subscriber, &0, &10)
+            .len(),
+        0
+    );
+
+    let key = SubscriptionKey {
+        subscriber: subscriber.clone(),
+        merchant: merchant.clone(),
+    };
+    client.migrate(&vec![&env], &vec![&env, key.clone()]);
+    assert_eq!(
+        client
+            .list_subscriptions_by_subscriber(&subscriber, &0, &10)
+            .len(),
+        1
+    );
+    assert_eq!(
+        client
+            .list_subscribers_by_merchant(&merchant, &0, &10)
+            .len(),
+        1
+    );
+
+    // Listing is idempotent
+    client.migrate(&vec![&env], &vec![&env, key]);
+    assert_eq!(
+        client
+            .list_subscribers_by_merchant(&merchant, &0, &10)
+            .len(),
+        1
+    );
+}
+
+// ============================================================================
+// PURGE TESTS
+// ============================================================================
+
+#[test]
+fn test_purge_finished_subscription() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    let amount: i128 = 100;
+    let interval: u64 = 30 * 24 * 60 * 60;
+    stellar_client.mint(&subscriber, &(amount * 10));
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
+
+    // Two payments, then cancel
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &amount,
+        &interval,
+    );
+    client.collect_payment(&subscriber, &merchant);
+    advance_time(&env, interval);
+    client.collect_payment(&subscriber, &merchant);
+    let last_payment_time = env.ledger().timestamp();
+    client.cancel_subscription(&subscriber, &merchant);
+
+    // Still inside the paid period: nothing to purge yet
+    let keys = vec![
+        &env,
+        SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        },
+    ];
+    assert_eq!(client.purge_subscriptions(&keys), 0);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::Cancelled
+    );
+
+    // Once the period and the last payment's dispute window end anyone can
+    // purge it
+    assert_eq!(
+        client.get_receipts(&subscriber, &merchant, &0, &10).len(),
+        2
+    );
+    advance_time(&env, interval);
+    assert_eq!(client.purge_subscriptions(&keys), 0);
+    advance_time(&env, 1);
+    assert_eq!(client.purge_subscriptions(&keys), 1);
+    assert_eq!(env.auths().len(), 0);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [SubscriptionPurged {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            credit_score: 20,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+
+    // The record and its receipts are gone but its credit is summarized
+    assert!(client.try_get_subscription(&subscriber, &merchant).is_err());
+    assert_eq!(
+        client.get_receipts(&subscriber, &merchant, &0, &10).len(),
+        0
+    );
+    assert_eq!(
+        client.get_credit_history(&subscriber),
+        CreditHistory {
+            closed_subscriptions: 1,
+            total_credit_score: 20,
+            best_credit_score: 20,
+            last_payment_time,
+        }
+    );
+}
+
+#[test]
+fn test_purge_skips_active_and_missing_subscriptions() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.create_subscription(&subscriber, &merchant, &token_client.address, &100, &3600);
+
+    let keys = vec![
+        &env,
+        SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        },
+        SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: Address::generate(&env),
+        },
+    ];
+    assert_eq!(client.purge_subscriptions(&keys), 0);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::Active
+    );
+    assert_eq!(
+        client.get_credit_history(&subscriber),
+        CreditHistory::default()
+    );
+}
+
+#[test]
+fn test_resubscribe_within_paid_period() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    let amount: i128 = 100;
+    let interval: u64 = 30 * 24 * 60 * 60;
+    stellar_client.mint(&subscriber, &(amount * 10));
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
+
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &amount,
+        &interval,
+    );
+    client.collect_payment(&subscriber, &merchant);
+    let paid_at = env.ledger().timestamp();
+    client.cancel_subscription(&subscriber, &merchant);
+
+    // Re-subscribing at a new price replaces the cancelled record but keeps
+    // counting from the payment already made for this period
+    advance_time(&env, 24 * 60 * 60);
+    let subscription = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &150,
+        &interval,
+    );
+    assert_eq!(subscription.status, SubscriptionStatus::Active);
+    assert_eq!(subscription.last_payment_time, paid_at);
+    assert_eq!(subscription.credit_score, 0);
+    assert!(!client.can_process_payment(&subscriber, &merchant));
+    assert_eq!(
+        client.get_credit_history(&subscriber).closed_subscriptions,
+        1
+    );
+
+    // After the period ends the next cancelled record starts afresh
+    client.cancel_subscription(&subscriber, &merchant);
+    advance_time(&env, interval);
+    let subscription = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &150,
+        &interval,
+    );
+    assert_eq!(subscription.last_payment_time, 0);
+    assert!(client.can_process_payment(&subscriber, &merchant));
+    assert_eq!(
+        client.get_credit_history(&subscriber),
+        CreditHistory {
+            closed_subscriptions: 2,
+            total_credit_score: 10,
+            best_credit_score: 10,
+            last_payment_time: paid_at,
+        }
+    );
+}
+
+// ============================================================================
+// RECEIPT TESTS
+// ============================================================================
+
+#[test]
+fn test_receipts_record_funding_source() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+
+    // Six paid collections reach credit score 60, then BNPL covers the seventh
+    let mut paid_at = Vec::new(&env);
+    for _ in 0..7 {
+        paid_at.push_back(env.ledger().timestamp());
+        client.collect_payment(&subscriber, &merchant);
+        advance_time(&env, 31 * 24 * 60 * 60);
+    }
+
+    let receipts = client.get_receipts(&subscriber, &merchant, &0, &10);
+    assert_eq!(receipts.len(), 7);
+    for (id, receipt) in receipts.iter().enumerate() {
+        assert_eq!(receipt.id, id as u32);
+        assert_eq!(receipt.token, token_address);
+        assert_eq!(receipt.amount, amount);
+        assert_eq!(receipt.timestamp, paid_at.get(id as u32).unwrap());
+    }
+    assert_eq!(receipts.get(5).unwrap().source, FundingSource::Balance);
+    assert_eq!(receipts.get(6).unwrap().source, FundingSource::Bnpl(0));
+
+    // Paging and single lookups
+    let page = client.get_receipts(&subscriber, &merchant, &5, &1);
+    assert_eq!(page.len(), 1);
+    assert_eq!(
+        page.get(0).unwrap(),
+        client.get_receipt(&subscriber, &merchant, &5)
+    );
+    assert_eq!(
+        client.get_receipts(&subscriber, &merchant, &7, &10).len(),
+        0
+    );
+}
+
+#[test]
+fn test_receipt_retention_is_bounded() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    let amount: i128 = 100;
+    let interval: u64 = 24 * 60 * 60;
+    stellar_client.mint(&subscriber, &(amount * 30));
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 30),
+        &expiration_ledger,
+    );
+
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &amount,
+        &interval,
+    );
+    collect_periods(&env, &client, &subscriber, &merchant, 30, interval);
+
+    // Receipts that can still be disputed are all kept
+    let receipts = client.get_receipts(&subscriber, &merchant, &0, &50);
+    assert_eq!(receipts.len(), 30);
+    assert_eq!(receipts.get(0).unwrap().id, 0);
+
+    // Past their dispute window, they are deleted two per collection until
+    // only the latest 24 are kept
+    advance_time(&env, DISPUTE_WINDOW);
+    stellar_client.mint(&subscriber, &(amount * 6));
+    token_client.approve(&subscriber, &contract_id, &(amount * 6), &expiration_ledger);
+    collect_periods(&env, &client, &subscriber, &merchant, 6, interval);
+    let receipts = client.get_receipts(&subscriber, &merchant, &0, &50);
+    assert_eq!(receipts.len(), 24);
+    assert_eq!(receipts.get(0).unwrap().id, 12);
+    assert_eq!(receipts.get(23).unwrap().id, 35);
+    assert!(client.try_get_receipt(&subscriber, &merchant, &11).is_err());
+
+    let key = SubscriptionKey {
+        subscriber: subscriber.clone(),
+        merchant: merchant.clone(),
+    };
+    env.as_contract(&contract_id, || {
+        assert!(!env
+            .storage()
+            .persistent()
+            .has(&DataKey::Receipt(key.clone(), 0)));
+        assert!(env.storage().persistent().has(&DataKey::Receipt(key, 12)));
+    });
+}
+
+#[test]
+fn test_receipts_are_capped() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+
+    let amount: i128 = 100;
+    let interval: u64 = 60 * 60;
+    stellar_client.mint(&subscriber, &(amount * 61));
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 61),
+        &expiration_ledger,
+    );
+
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &amount,
+        &interval,
+    );
+    client.collect_payment(&subscriber, &merchant);
+    client.open_dispute(&subscriber, &merchant, &0, &50);
+    client.escrow_dispute(&subscriber, &merchant, &0);
+    advance_time(&env, interval);
+
+    // Hourly payments are all still disputable, but only the latest 48
+    // receipts are kept
+    collect_periods(&env, &client, &subscriber, &merchant, 60, interval);
+    let receipts = client.get_receipts(&subscriber, &merchant, &13, &50);
+    assert_eq!(receipts.len(), 48);
+    assert_eq!(receipts.get(0).unwrap().id, 13);
+    assert!(client.try_get_receipt(&subscriber, &merchant, &12).is_err());
+    assert!(client.try_get_receipt(&subscriber, &merchant, &0).is_err());
+
+    // The open dispute outlives its receipt and can still be resolved
+    assert!(client.get_dispute(&subscriber, &merchant, &0).is_some());
+    let dispute = client.resolve_dispute(&contract_admin, &subscriber, &merchant, &0, &true);
+    assert_eq!(dispute.status, DisputeStatus::ResolvedForSubscriber);
+    assert_eq!(token_client.balance(&subscriber), 50);
+    assert_eq!(client.get_dispute(&subscriber, &merchant, &0), None);
+
+    let key = SubscriptionKey {
+        subscriber: subscriber.clone(),
+        merchant: merchant.clone(),
+    };
+    env.as_contract(&contract_id, || {
+        assert!(!env.storage().persistent().has(&DataKey::OpenDisputes(key)));
+    });
+}
+
+// ============================================================================
+// REFUND TESTS
+// ============================================================================
+
+#[test]
+fn test_partial_refunds_to_subscriber() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    let amount: i128 = 100;
+    stellar_client.mint(&subscriber, &amount);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &amount, &expiration_ledger);
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token,
+        &amount,
+        &(30 * 24 * 60 * 60),
+    );
+    client.collect_payment(&subscriber, &merchant);
+
+    // The merchant refunds part of the payment...
+    let receipt = client.refund(&subscriber, &merchant, &0, &30);
+    assert_eq!(env.auths()[0].0, merchant);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [Refunded {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            receipt_id: 0,
+            token: token.clone(),
+            amount: 30,
+            to_debt: 0,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(receipt.refunded, 30);
+    assert_eq!(token_client.balance(&subscriber), 30);
+    assert_eq!(token_client.balance(&merchant), 70);
+
+    // ...then the rest, and no more
+    client.refund(&subscriber, &merchant, &0, &70);
+    assert_eq!(
+        client.get_receipt(&subscriber, &merchant, &0).refunded,
+        amount
+    );
+    assert_eq!(token_client.balance(&subscriber), amount);
+    assert!(client.try_refund(&subscriber, &merchant, &0, &1).is_err());
+
+    // Refunds don't touch the subscriber's credit
+    assert_eq!(client.get_credit_score(&subscriber, &merchant), 10);
+}
+
+#[test]
+#[should_panic(expected = "Refund exceeds receipt amount")]
+fn test_refund_exceeding_receipt() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&subscriber, &100);
+    stellar_client.mint(&merchant, &1_000);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token_client.address, &100, &3600);
+    client.collect_payment(&subscriber, &merchant);
+
+    client.refund(&subscriber, &merchant, &0, &101);
+}
+
+#[test]
+fn test_protocol_fee_is_not_refundable() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let arbiter = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    apply_param_change(&env, &client, ParamChange::Treasury(treasury.clone()));
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::MerchantFee(merchant.clone(), Some(500)),
+    );
+    client.grant_role(&Role::Arbiter, &arbiter);
+
+    stellar_client.mint(&subscriber, &1_000);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &1_000, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &1_000, &3600);
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(token_client.balance(&merchant), 950);
+    assert_eq!(token_client.balance(&treasury), 50);
+
+    // The merchant can refund what it received, not the fee
+    assert!(client.try_refund(&subscriber, &merchant, &0, &951).is_err());
+    client.refund(&subscriber, &merchant, &0, &900);
+    assert_eq!(
+        client.get_receipt(&subscriber, &merchant, &0).refundable(),
+        50
+    );
+
+    // Disputes are capped the same way
+    assert!(client
+        .try_open_dispute(&subscriber, &merchant, &0, &51)
+        .is_err());
+    client.open_dispute(&subscriber, &merchant, &0, &50);
+    client.escrow_dispute(&subscriber, &merchant, &0);
+    client.resolve_dispute(&arbiter, &subscriber, &merchant, &0, &true);
+    assert_eq!(token_client.balance(&subscriber), 950);
+    assert_eq!(token_client.balance(&merchant), 0);
+    assert_eq!(token_client.balance(&treasury), 50);
+    assert!(client.try_refund(&subscriber, &merchant, &0, &1).is_err());
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_refund_requires_merchant() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&subscriber, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token_client.address, &100, &3600);
+    client.collect_payment(&subscriber, &merchant);
+
+    // The subscriber can't refund themselves
+    client
+        .mock_auths(&[MockAuth {
+            address: &subscriber,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "refund",
+                args: (subscriber.clone(), merchant.clone(), 0u32, 50i128).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .refund(&subscriber, &merchant, &0, &50);
+}
+
+#[test]
+fn test_refund_of_bnpl_payment_reduces_debt() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let lp = Address::generate(&env);
+
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token_address = token_client.address.clone();
+
+    let amount: i128 = 100_0000000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token_address, &(amount * 100));
+
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 20),
+        &expiration_ledger,
+    );
+
+    let interval: u64 = 30 * 24 * 60 * 60;
+    client.create_subscription(&subscriber, &merchant, &token_address, &amount, &interval);
+
+    // Six paid collections, then BNPL covers the seventh
+    collect_periods(&env, &client, &subscriber, &merchant, 7, 31 * 24 * 60 * 60);
+    let fee = amount / 100;
+    assert_eq!(
+        client.get_user_debt(&subscriber).unwrap().amount,
+        amount + fee
+    );
+
+    // Refunding the pool-funded payment repays the debt it created; the
+    // subscriber receives nothing directly
+    let receipt = client.refund(&subscriber, &merchant, &6, &amount);
+    assert_eq!(receipt.source, FundingSource::Bnpl(0));
+    assert_eq!(receipt.refunded, amount);
+    assert_eq!(client.get_user_debt(&subscriber).unwrap().amount, fee);
+    assert_eq!(client.get_advance(&subscriber, &0).repaid, amount);
+    assert_eq!(token_client.balance(&subscriber), 0);
+    assert_eq!(pool.get_reserves(&token_address).borrowed, 0);
+}
+
+// ============================================================================
+// DISPUTE TESTS
+// ============================================================================
+
+#[test]
+fn test_dispute_resolved_for_subscriber() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let arbiter = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    client.grant_role(&Role::Arbiter, &arbiter);
+
+    stellar_client.mint(&subscriber, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token_client.address, &100, &3600);
+    client.collect_payment(&subscriber, &merchant);
+
+    // The subscriber disputes part of the payment
+    let dispute = client.open_dispute(&subscriber, &merchant, &0, &60);
+    assert_eq!(env.auths()[0].0, subscriber);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [DisputeOpened {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            receipt_id: 0,
+            amount: 60,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(dispute.status, DisputeStatus::Open);
+    assert!(client
+        .try_open_dispute(&subscriber, &merchant, &0, &10)
+        .is_err());
+
+    // The merchant escrows the disputed funds
+    client.escrow_dispute(&subscriber, &merchant, &0);
+    assert_eq!(env.auths()[0].0, merchant);
+    assert_eq!(token_client.balance(&contract_id), 60);
+    assert_eq!(token_client.balance(&merchant), 40);
+
+    // Open disputes keep the subscription from being purged
+    client.cancel_subscription(&subscriber, &merchant);
+    advance_time(&env, 3600);
+    let keys = vec![
+        &env,
+        SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        },
+    ];
+    assert_eq!(client.purge_subscriptions(&keys), 0);
+
+    // The arbiter sides with the subscriber
+    let dispute = client.resolve_dispute(&arbiter, &subscriber, &merchant, &0, &true);
+    assert_eq!(env.auths()[0].0, arbiter);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [DisputeResolved {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            receipt_id: 0,
+            arbiter: arbiter.clone(),
+            status: DisputeStatus::ResolvedForSubscriber,
+            refunded: 60,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(dispute.status, DisputeStatus::ResolvedForSubscriber);
+    assert_eq!(token_client.balance(&subscriber), 60);
+    assert_eq!(token_client.balance(&contract_id), 0);
+    assert_eq!(client.get_receipt(&subscriber, &merchant, &0).refunded, 60);
+    assert_eq!(client.get_merchant_disputes(&merchant).lost, 1);
+    assert!(client
+        .try_resolve_dispute(&arbiter, &subscriber, &merchant, &0, &false)
+        .is_err());
+
+    // Once resolved and out of the dispute window, the subscription can be
+    // purged along with the dispute
+    assert_eq!(client.purge_subscriptions(&keys), 0);
+    advance_time(&env, DISPUTE_WINDOW);
+    assert_eq!(client.purge_subscriptions(&keys), 1);
+    assert_eq!(client.get_dispute(&subscriber, &merchant, &0), None);
+}
+
+#[test]
+fn test_dispute_resolved_for_merchant() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&subscriber, &200);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &200, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token_client.address, &100, &3600);
+    client.collect_payment(&subscriber, &merchant);
+    advance_time(&env, 3600);
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(client.get_credit_score(&subscriber, &merchant), 20);
+
+    client.open_dispute(&subscriber, &merchant, &1, &100);
+    client.escrow_dispute(&subscriber, &merchant, &1);
+
+    // The admin arbitrates; the merchant keeps the payment
+    let dispute = client.resolve_dispute(&client.get_admin(), &subscriber, &merchant, &1, &false);
+    assert_eq!(dispute.status, DisputeStatus::ResolvedForMerchant);
+    assert_eq!(token_client.balance(&merchant), 200);
+    assert_eq!(token_client.balance(&contract_id), 0);
+    assert_eq!(client.get_receipt(&subscriber, &merchant, &1).refunded, 0);
+    assert_eq!(client.get_merchant_disputes(&merchant).won, 1);
+
+    // The lost dispute costs the subscriber credit
+    assert_eq!(client.get_credit_score(&subscriber, &merchant), 0);
+}
+
+#[test]
+#[should_panic(expected = "Dispute window has closed")]
+fn test_dispute_window_closes() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&subscriber, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &100,
+        &(90 * 24 * 60 * 60),
+    );
+    client.collect_payment(&subscriber, &merchant);
+
+    advance_time(&env, 30 * 24 * 60 * 60 + 1);
+    client.open_dispute(&subscriber, &merchant, &0, &100);
+}
+
+#[test]
+#[should_panic(expected = "Caller does not have the required role")]
+fn test_resolve_dispute_requires_arbiter() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&subscriber, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token_client.address, &100, &3600);
+    client.collect_payment(&subscriber, &merchant);
+    client.open_dispute(&subscriber, &merchant, &0, &100);
+
+    // Neither party can decide its own dispute
+    client.resolve_dispute(&merchant, &subscriber, &merchant, &0, &false);
+}
+
+#[test]
+fn test_merchant_arbiter_overrides_global_arbiters() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let merchant_arbiter = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+
+    client.set_merchant_arbiter(&contract_admin, &merchant, &Some(merchant_arbiter.clone()));
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [MerchantArbiterUpdated {
+            version: EVENT_VERSION,
+            caller: contract_admin.clone(),
+            merchant: merchant.clone(),
+            arbiter: Some(merchant_arbiter.clone()),
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(
+        client.get_merchant_arbiter(&merchant),
+        Some(merchant_arbiter.clone())
+    );
+
+    stellar_client.mint(&subscriber, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token_client.address, &100, &3600);
+    client.collect_payment(&subscriber, &merchant);
+    client.open_dispute(&subscriber, &merchant, &0, &100);
+
+    // Even the admin defers to the merchant's own arbiter
+    assert!(client
+        .try_resolve_dispute(&contract_admin, &subscriber, &merchant, &0, &true)
+        .is_err());
+
+    client.escrow_dispute(&subscriber, &merchant, &0);
+    let dispute = client.resolve_dispute(&merchant_arbiter, &subscriber, &merchant, &0, &true);
+    assert_eq!(dispute.status, DisputeStatus::ResolvedForSubscriber);
+    assert_eq!(token_client.balance(&subscriber), 100);
+    assert_eq!(client.get_merchant_disputes(&merchant).lost, 1);
+
+    // Clearing the arbiter hands disputes back to the global arbiters
+    client.set_merchant_arbiter(&contract_admin, &merchant, &None);
+    assert_eq!(client.get_merchant_arbiter(&merchant), None);
+}
+
+#[test]
+fn test_unescrowed_dispute_freezes_merchant() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+
+    stellar_client.mint(&subscriber, &300);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &300, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &100, &3600);
+    client.collect_payment(&subscriber, &merchant);
+    client.open_dispute(&subscriber, &merchant, &0, &100);
+
+    // Nothing can be decided, collected or withdrawn until it is escrowed
+    advance_time(&env, 3600);
+    assert!(client
+        .try_resolve_dispute(&contract_admin, &subscriber, &merchant, &0, &true)
+        .is_err());
+    assert!(client.try_collect_payment(&subscriber, &merchant).is_err());
+    assert!(client.try_withdraw_settlement(&merchant, &token).is_err());
+
+    client.escrow_dispute(&subscriber, &merchant, &0);
+    assert_eq!(client.collect_payment(&subscriber, &merchant), 100);
+    client.resolve_dispute(&contract_admin, &subscriber, &merchant, &0, &true);
+    assert_eq!(token_client.balance(&subscriber), 200);
+}
+
+// ============================================================================
+// SETTLEMENT TESTS
+// ============================================================================
+
+#[test]
+fn test_settlement_delay_holds_payments() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+
+    let delay: u64 = 7 * 24 * 60 * 60;
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), delay),
+    );
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [SettlementDelayUpdated {
+            version: EVENT_VERSION,
+            proposer: contract_admin.clone(),
+            merchant: merchant.clone(),
+            delay,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_settlement_delay(&merchant), delay);
+
+    stellar_client.mint(&subscriber, &200);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &200, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &100, &(24 * 60 * 60));
+
+    // Payments are held by the contract instead of reaching the merchant
+    client.collect_payment(&subscriber, &merchant);
+    advance_time(&env, 24 * 60 * 60);
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(token_client.balance(&merchant), 0);
+    assert_eq!(token_client.balance(&contract_id), 200);
+    assert_eq!(client.get_settlement(&merchant, &token).unsettled, 200);
+    assert_eq!(client.get_receipt(&subscriber, &merchant, &1).amount, 100);
+
+    // Only the first payment has waited out the delay
+    advance_time(&env, delay - 24 * 60 * 60);
+    assert_eq!(client.withdraw_settlement(&merchant, &token), 100);
+    assert_eq!(env.auths()[0].0, merchant);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [Settled {
+            merchant: merchant.clone(),
+            token: token.clone(),
+            version: EVENT_VERSION,
+            amount: 100,
+            holds: 1,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_hold(&merchant, &token, &0), None);
+    assert_eq!(client.withdraw_settlement(&merchant, &token), 0);
+
+    advance_time(&env, 24 * 60 * 60);
+    assert_eq!(client.withdraw_settlement(&merchant, &token), 100);
+    assert_eq!(token_client.balance(&merchant), 200);
+    assert_eq!(token_client.balance(&contract_id), 0);
+
+    let settlement = client.get_settlement(&merchant, &token);
+    assert_eq!(settlement.unsettled, 0);
+    assert_eq!(settlement.next_release, 2);
+    assert_eq!(settlement.next_hold, 2);
+
+    // Clearing the delay pays the merchant directly again
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), 0),
+    );
+    assert_eq!(client.get_settlement_delay(&merchant), 0);
+}
+
+#[test]
+fn test_refund_served_from_unsettled_funds() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), 3600),
+    );
+
+    stellar_client.mint(&subscriber, &100);
+    stellar_client.mint(&merchant, &50);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &100, &(24 * 60 * 60));
+    client.collect_payment(&subscriber, &merchant);
+
+    // The refund comes out of the held payment, not the merchant's wallet
+    client.refund(&subscriber, &merchant, &0, &30);
+    assert_eq!(token_client.balance(&subscriber), 30);
+    assert_eq!(token_client.balance(&merchant), 50);
+    assert_eq!(client.get_settlement(&merchant, &token).unsettled, 70);
+    assert_eq!(client.get_hold(&merchant, &token, &0).unwrap().amount, 70);
+
+    // Once the payment has settled, refunds are paid by the merchant
+    advance_time(&env, 3600);
+    assert_eq!(client.withdraw_settlement(&merchant, &token), 70);
+    client.refund(&subscriber, &merchant, &0, &20);
+    assert_eq!(token_client.balance(&subscriber), 50);
+    assert_eq!(token_client.balance(&merchant), 100);
+    assert_eq!(token_client.balance(&contract_id), 0);
+}
+
+#[test]
+fn test_dispute_escrows_unsettled_funds() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), 3600),
+    );
+
+    stellar_client.mint(&subscriber, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &100, &(24 * 60 * 60));
+    client.collect_payment(&subscriber, &merchant);
+
+    // The disputed part of a held payment is escrowed right away
+    let dispute = client.open_dispute(&subscriber, &merchant, &0, &60);
+    assert_eq!(dispute.escrowed, 60);
+    assert_eq!(client.get_settlement(&merchant, &token).unsettled, 40);
+
+    // The rest still settles to the merchant
+    advance_time(&env, 3600);
+    assert_eq!(client.withdraw_settlement(&merchant, &token), 40);
+    assert_eq!(token_client.balance(&contract_id), 60);
+
+    client.resolve_dispute(&contract_admin, &subscriber, &merchant, &0, &true);
+    assert_eq!(token_client.balance(&subscriber), 60);
+    assert_eq!(token_client.balance(&merchant), 40);
+    assert_eq!(token_client.balance(&contract_id), 0);
+}
+
+#[test]
+fn test_refund_after_resubscribe_leaves_old_hold() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let day: u64 = 24 * 60 * 60;
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), 90 * day),
+    );
+
+    stellar_client.mint(&subscriber, &200);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &200, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &100, &day);
+    client.collect_payment(&subscriber, &merchant);
+    client.cancel_subscription(&subscriber, &merchant);
+
+    // The subscription is purged while its payment is still held
+    advance_time(&env, 31 * day);
+    let keys = vec![
+        &env,
+        SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        },
+    ];
+    assert_eq!(client.purge_subscriptions(&keys), 1);
+    assert_eq!(client.get_hold(&merchant, &token, &0).unwrap().amount, 100);
+
+    // The new subscription's receipt 0 is paid out directly, so refunding
+    // it falls on the merchant rather than the old subscription's hold
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), 0),
+    );
+    client.create_subscription(&subscriber, &merchant, &token, &100, &day);
+    client.collect_payment(&subscriber, &merchant);
+    client.refund(&subscriber, &merchant, &0, &40);
+    assert_eq!(token_client.balance(&subscriber), 40);
+    assert_eq!(token_client.balance(&merchant), 60);
+    assert_eq!(client.get_hold(&merchant, &token, &0).unwrap().amount, 100);
+    assert_eq!(client.get_settlement(&merchant, &token).unsettled, 100);
+}
+
+#[test]
+fn test_old_hold_withdrawal_keeps_resubscribed_hold() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let day: u64 = 24 * 60 * 60;
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), 90 * day),
+    );
+
+    stellar_client.mint(&subscriber, &200);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &200, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &100, &day);
+    client.collect_payment(&subscriber, &merchant);
+    client.cancel_subscription(&subscriber, &merchant);
+    advance_time(&env, 31 * day);
+    let keys = vec![
+        &env,
+        SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        },
+    ];
+    assert_eq!(client.purge_subscriptions(&keys), 1);
+
+    // The new subscription's receipt 0 is held as hold 1
+    client.create_subscription(&subscriber, &merchant, &token, &100, &day);
+    client.collect_payment(&subscriber, &merchant);
+    let hold = client.get_hold(&merchant, &token, &1).unwrap();
+    assert_eq!(hold.receipt_id, 0);
+    assert_eq!(hold.collected_at, env.ledger().timestamp());
+
+    // Withdrawing the old subscription's hold leaves the new one's link, so
+    // its refund still comes out of hold 1
+    advance_time(&env, 59 * day);
+    assert_eq!(client.withdraw_settlement(&merchant, &token), 100);
+    client.refund(&subscriber, &merchant, &0, &40);
+    assert_eq!(token_client.balance(&subscriber), 40);
+    assert_eq!(token_client.balance(&merchant), 100);
+    assert_eq!(client.get_hold(&merchant, &token, &1).unwrap().amount, 60);
+    assert_eq!(client.get_settlement(&merchant, &token).unsettled, 60);
+}
+
+#[test]
+#[should_panic(expected = "Settlement delay too long")]
+fn test_settlement_delay_is_capped() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), 91 * 24 * 60 * 60),
+    );
+}
+
+// ============================================================================
+// PROTOCOL FEE TESTS
+// ============================================================================
+
+#[test]
+fn test_protocol_fee_routed_to_treasury() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+
+    // The fee change waits out the timelock like any other parameter
+    let pending = client.queue_param_change(&contract_admin, &ParamChange::ProtocolFee(250));
+    advance_time(&env, 48 * 60 * 60);
+    client.execute_param_change(&pending.id);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [ProtocolFeeUpdated {
+            version: EVENT_VERSION,
+            proposer: contract_admin.clone(),
+            fee_bps: 250,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_protocol_fee(), 250);
+
+    stellar_client.mint(&subscriber, &2_000);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &2_000, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &1_000, &3600);
+
+    // Without a treasury nothing is charged
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(token_client.balance(&merchant), 1_000);
+    assert_eq!(client.get_receipt(&subscriber, &merchant, &0).fee, 0);
+
+    apply_param_change(&env, &client, ParamChange::Treasury(treasury.clone()));
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [TreasuryUpdated {
+            version: EVENT_VERSION,
+            proposer: contract_admin.clone(),
+            treasury: treasury.clone(),
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_treasury(), Some(treasury.clone()));
+
+    // 2.5% of the payment goes to the treasury
+    advance_time(&env, 3600);
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [
+            ProtocolFeeCharged {
+                merchant: merchant.clone(),
+                token: token.clone(),
+                version: EVENT_VERSION,
+                gross: 1_000,
+                fee: 25,
+                net: 975,
+                treasury: treasury.clone(),
+            }
+            .to_xdr(&env, &contract_id),
+            PaymentCollected {
+                subscriber: subscriber.clone(),
+                merchant: merchant.clone(),
+                version: EVENT_VERSION,
+                token: token.clone(),
+                amount: 1_000,
+                credit_score: 20,
+            }
+            .to_xdr(&env, &contract_id),
+        ]
+    );
+    assert_eq!(token_client.balance(&treasury), 25);
+    assert_eq!(token_client.balance(&merchant), 1_975);
+    assert_eq!(token_client.balance(&subscriber), 0);
+
+    let receipt = client.get_receipt(&subscriber, &merchant, &1);
+    assert_eq!(receipt.amount, 1_000);
+    assert_eq!(receipt.fee, 25);
+    assert_eq!(client.get_protocol_fees(&token), 25);
+}
+
+#[test]
+fn test_merchant_fee_override() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+    apply_param_change(&env, &client, ParamChange::Treasury(treasury.clone()));
+
+    // Merchants pay 1% unless they have an override
+    let pending = client.queue_param_change(&contract_admin, &ParamChange::ProtocolFee(100));
+    advance_time(&env, 48 * 60 * 60);
+    client.execute_param_change(&pending.id);
+
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::MerchantFee(merchant.clone(), Some(500)),
+    );
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [MerchantFeeUpdated {
+            version: EVENT_VERSION,
+            proposer: contract_admin.clone(),
+            merchant: merchant.clone(),
+            fee_bps: Some(500),
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_merchant_fee(&merchant), 500);
+
+    stellar_client.mint(&subscriber, &3_000);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &3_000, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &1_000, &3600);
+
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(token_client.balance(&treasury), 50);
+
+    // A zero override waives the fee entirely
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::MerchantFee(merchant.clone(), Some(0)),
+    );
+    advance_time(&env, 3600);
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(token_client.balance(&treasury), 50);
+
+    // Clearing the override falls back to the protocol fee
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::MerchantFee(merchant.clone(), None),
+    );
+    assert_eq!(client.get_merchant_fee(&merchant), 100);
+    advance_time(&env, 3600);
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(token_client.balance(&treasury), 60);
+    assert_eq!(token_client.balance(&merchant), 2_940);
+    assert_eq!(client.get_protocol_fees(&token), 60);
+}
+
+#[test]
+fn test_bnpl_collection_pays_protocol_fee() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let lp = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+
+    let amount: i128 = 1_000;
+    stellar_client.mint(&subscriber, &(amount * 6));
+    stellar_client.mint(&lp, &(amount * 100));
+
+    let (contract_id, client, pool) = setup_contract_with_pool(&env);
+    pool.deposit(&lp, &token, &(amount * 100));
+    apply_param_change(&env, &client, ParamChange::Treasury(treasury.clone()));
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::MerchantFee(merchant.clone(), Some(200)),
+    );
+
+    let expiration_ledger = env.ledger().sequence() + 5000;
+    token_client.approve(&subscriber, &contract_id, &(amount * 6), &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &amount, &3600);
+    collect_periods(&env, &client, &subscriber, &merchant, 6, 3600);
+    assert_eq!(token_client.balance(&treasury), 120);
+
+    // The vault fronts the whole payment, fee included
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(token_client.balance(&treasury), 140);
+    assert_eq!(token_client.balance(&merchant), amount * 7 - 140);
+    assert_eq!(
+        client.get_user_debt(&subscriber).unwrap().amount,
+        amount + amount / 100
+    );
+    assert_eq!(client.get_receipt(&subscriber, &merchant, &6).fee, 20);
+}
+
+#[test]
+#[should_panic(expected = "Protocol fee too high")]
+fn test_protocol_fee_is_capped() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.queue_param_change(&client.get_admin(), &ParamChange::ProtocolFee(1_001));
+}
+
+// ============================================================================
+// REVENUE SPLIT TESTS
+// ============================================================================
+
+#[test]
+fn test_revenue_split_pays_each_recipient() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let partner = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    let splits = vec![
+        &env,
+        Split {
+            recipient: merchant.clone(),
+            share_bps: 3_334,
+        },
+        Split {
+            recipient: partner.clone(),
+            share_bps: 3_333,
+        },
+        Split {
+            recipient: creator.clone(),
+            share_bps: 3_333,
+        },
+    ];
+    client.set_revenue_split(&merchant, &splits);
+    assert_eq!(env.auths()[0].0, merchant);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [RevenueSplitUpdated {
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            splits: splits.clone(),
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!(client.get_revenue_split(&merchant), splits);
+
+    stellar_client.mint(&subscriber, &1_000);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &1_000, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &1_000, &3600);
+    client.collect_payment(&subscriber, &merchant);
+
+    // 333.4 / 333.3 / 333.3 round down to 333 each; the first recipient
+    // gets the leftover unit
+    assert_eq!(token_client.balance(&merchant), 334);
+    assert_eq!(token_client.balance(&partner), 333);
+    assert_eq!(token_client.balance(&creator), 333);
+    assert_eq!(token_client.balance(&contract_id), 0);
+
+    // Clearing the split pays the merchant directly again
+    client.set_revenue_split(&merchant, &Vec::new(&env));
+    assert_eq!(client.get_revenue_split(&merchant).len(), 0);
+}
+
+#[test]
+fn test_revenue_split_applies_after_fee_and_settlement() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let partner = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    apply_param_change(&env, &client, ParamChange::Treasury(treasury.clone()));
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::MerchantFee(merchant.clone(), Some(100)),
+    );
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), 3600),
+    );
+
+    client.set_revenue_split(
+        &merchant,
+        &vec![
+            &env,
+            Split {
+                recipient: merchant.clone(),
+                share_bps: 7_500,
+            },
+            Split {
+                recipient: partner.clone(),
+                share_bps: 2_500,
+            },
+        ],
+    );
+
+    stellar_client.mint(&subscriber, &1_001);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &1_001, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &1_001, &(24 * 60 * 60));
+    client.collect_payment(&subscriber, &merchant);
+
+    // The fee is taken first and the rest is held for the payees
+    assert_eq!(token_client.balance(&treasury), 10);
+    assert_eq!(token_client.balance(&contract_id), 991);
+
+    // 743.25 / 247.75 round down; the leftover unit goes to the merchant
+    advance_time(&env, 3600);
+    assert_eq!(client.withdraw_settlement(&merchant, &token), 991);
+    assert_eq!(token_client.balance(&merchant), 744);
+    assert_eq!(token_client.balance(&partner), 247);
+    assert_eq!(token_client.balance(&contract_id), 0);
+}
+
+#[test]
+fn test_revenue_split_applies_to_dispute_won_by_merchant() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let partner = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+    apply_param_change(
+        &env,
+        &client,
+        ParamChange::SettlementDelay(merchant.clone(), 3600),
+    );
+    client.set_revenue_split(
+        &merchant,
+        &vec![
+            &env,
+            Split {
+                recipient: merchant.clone(),
+                share_bps: 6_000,
+            },
+            Split {
+                recipient: partner.clone(),
+                share_bps: 4_000,
+            },
+        ],
+    );
+
+    stellar_client.mint(&subscriber, &1_000);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &1_000, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &1_000, &(24 * 60 * 60));
+    client.collect_payment(&subscriber, &merchant);
+
+    // Half the held payment goes into escrow, the rest settles as usual
+    let dispute = client.open_dispute(&subscriber, &merchant, &0, &500);
+    assert_eq!(dispute.held, 500);
+    advance_time(&env, 3600);
+    assert_eq!(client.withdraw_settlement(&merchant, &token), 500);
+    assert_eq!(token_client.balance(&merchant), 300);
+    assert_eq!(token_client.balance(&partner), 200);
+
+    // Winning the dispute releases the escrow to the payees too
+    client.resolve_dispute(&contract_admin, &subscriber, &merchant, &0, &false);
+    assert_eq!(token_client.balance(&merchant), 600);
+    assert_eq!(token_client.balance(&partner), 400);
+    assert_eq!(token_client.balance(&contract_id), 0);
+}
+
+#[test]
+#[should_panic(expected = "Split shares must sum to 100%")]
+fn test_revenue_split_must_sum_to_100_percent() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let merchant = Address::generate(&env);
+    let partner = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.set_revenue_split(
+        &merchant,
+        &vec![
+            &env,
+            Split {
+                recipient: merchant.clone(),
+                share_bps: 6_000,
+            },
+            Split {
+                recipient: partner.clone(),
+                share_bps: 3_000,
+            },
+        ],
+    );
+}
+
+#[test]
+#[should_panic(expected = "Split shares must be positive")]
+fn test_revenue_split_rejects_empty_shares() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let merchant = Address::generate(&env);
+    let partner = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.set_revenue_split(
+        &merchant,
+        &vec![
+            &env,
+            Split {
+                recipient: merchant.clone(),
+                share_bps: 10_000,
+            },
+            Split {
+                recipient: partner.clone(),
+                share_bps: 0,
+            },
+        ],
+    );
+}
+
+// ============================================================================
+// GIFT TESTS
+// ============================================================================
+
+#[test]
+fn test_gift_subscription_charges_payer() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let payer = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&payer, &100);
+    stellar_client.mint(&subscriber, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&payer, &contract_id, &100, &expiration_ledger);
+
+    // A parent pays for a child's subscription
+    let subscription =
+        client.create_gift_subscription(&payer, &subscriber, &merchant, &token, &100, &3600);
+    assert_eq!(env.auths()[0].0, payer);
+    assert_eq!(env.auths()[1].0, subscriber);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [
+            SubscriptionCreated {
+                subscriber: subscriber.clone(),
+                merchant: merchant.clone(),
+                version: EVENT_VERSION,
+                token: token.clone(),
+                amount: 100,
+                interval: 3600,
+            }
+            .to_xdr(&env, &contract_id),
+            SubscriptionGifted {
+                subscriber: subscriber.clone(),
+                merchant: merchant.clone(),
+                version: EVENT_VERSION,
+                payer: payer.clone(),
+            }
+            .to_xdr(&env, &contract_id),
+        ]
+    );
+    assert_eq!(subscription.subscriber, subscriber);
+    assert_eq!(subscription.payer, payer);
+
+    // It is listed under the subscriber, not the payer
+    assert_eq!(
+        client
+            .list_subscriptions_by_subscriber(&subscriber, &0, &10)
+            .len(),
+        1
+    );
+    assert_eq!(
+        client
+            .list_subscriptions_by_subscriber(&payer, &0, &10)
+            .len(),
+        0
+    );
+
+    // Payments come out of the payer's balance
+    client.collect_payment(&subscriber, &merchant);
+    assert_eq!(token_client.balance(&payer), 0);
+    assert_eq!(token_client.balance(&subscriber), 100);
+    assert_eq!(token_client.balance(&merchant), 100);
+    assert_eq!(client.get_credit_score(&subscriber, &merchant), 10);
+
+    // Refunds go back to whoever paid
+    client.refund(&subscriber, &merchant, &0, &40);
+    assert_eq!(token_client.balance(&payer), 40);
+    assert_eq!(token_client.balance(&subscriber), 100);
+
+    // The payer manages billing
+    client.pause_subscription(&subscriber, &merchant);
+    assert_eq!(env.auths()[0].0, payer);
+    client.cancel_subscription(&subscriber, &merchant);
+    assert_eq!(env.auths()[0].0, payer);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_gift_subscription_cancelled_only_by_payer() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let payer = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    client.create_gift_subscription(
+        &payer,
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &100,
+        &3600,
+    );
+
+    client
+        .mock_auths(&[MockAuth {
+            address: &subscriber,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "cancel_subscription",
+                args: (&subscriber, &merchant).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .cancel_subscription(&subscriber, &merchant);
+}
+
+#[test]
+fn test_gift_subscription_disputed_by_subscriber() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let payer = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let contract_admin = client.get_admin();
+
+    stellar_client.mint(&payer, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&payer, &contract_id, &100, &expiration_ledger);
+    client.create_gift_subscription(&payer, &subscriber, &merchant, &token, &100, &3600);
+    client.collect_payment(&subscriber, &merchant);
+
+    // The subscriber who received the service disputes it
+    client.open_dispute(&subscriber, &merchant, &0, &60);
+    assert_eq!(env.auths()[0].0, subscriber);
+    client.escrow_dispute(&subscriber, &merchant, &0);
+
+    // The refund goes back to the payer
+    client.resolve_dispute(&contract_admin, &subscriber, &merchant, &0, &true);
+    assert_eq!(token_client.balance(&payer), 60);
+    assert_eq!(token_client.balance(&subscriber), 0);
+    assert_eq!(token_client.balance(&merchant), 40);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_gift_subscription_requires_subscriber_auth() {
+    let env = Env::default();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let payer = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    let token = token_client.address.clone();
+
+    // Only the payer signs; a gift can't be forced on the subscriber
+    client
+        .mock_auths(&[MockAuth {
+            address: &payer,
+            invoke: &MockAuthInvoke {
+                contract: &contract_id,
+                fn_name: "create_gift_subscription",
+                args: (&payer, &subscriber, &merchant, &token, 100_i128, 3600_u64).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .create_gift_subscription(&payer, &subscriber, &merchant, &token, &100, &3600);
+}
+
+#[test]
+fn test_upgrade_sets_payer_on_v4_subscriptions() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+
+    let contract_id = env.register(SoroSubV1, ());
+    let v4 = SoroSubV1Client::new(&env, &contract_id);
+    v4.initialize(&admin, &liquidity_pool);
+    v4.record_subscription_v4(&SubscriptionV4 {
+        subscriber: subscriber.clone(),
+        merchant: merchant.clone(),
+        token: token_client.address.clone(),
+        amount: 100,
+        interval: 3600,
+        starts_at: 800,
+        last_payment_time: 900,
+        status: SubscriptionStatus::Paused,
+        credit_score: 20,
+    });
+
+    // v4 subscriptions were paid for by their subscriber
+    let client = upgrade_to_current(&env, &contract_id);
+    let subscription = client.get_subscription(&subscriber, &merchant);
+    assert_eq!(subscription.payer, subscriber);
+    assert_eq!(subscription.starts_at, 800);
+    assert_eq!(subscription.last_payment_time, 900);
+    assert_eq!(subscription.status, SubscriptionStatus::Paused);
+    assert_eq!(subscription.credit_score, 20);
+
+    let keys = vec![
+        &env,
+        SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+        },
+    ];
+    assert_eq!(client.migrate(&vec![&env], &keys), 1);
+    assert_eq!(client.migrate(&vec![&env], &keys), 0);
+    assert_eq!(client.get_schema_version(), SCHEMA_VERSION);
+}
+
+// ============================================================================
+// TEAM TESTS
+// ============================================================================
+
+#[test]
+fn test_team_members_share_owner_access() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let owner = Address::generate(&env);
+    let member = Address::generate(&env);
+    let outsider = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&owner, &1000);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&owner, &contract_id, &1000, &expiration_ledger);
+
+    // Three seats at 100 each are billed to the owner
+    let subscription = client.create_team_subscription(&owner, &merchant, &token, &100, &3, &3600);
+    assert_eq!(subscription.amount, 300);
+    client.collect_payment(&owner, &merchant);
+    assert_eq!(token_client.balance(&owner), 700);
+
+    client.add_member(&owner, &merchant, &member);
+    assert_eq!(env.auths()[0].0, owner);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [MemberAdded {
+            owner: owner.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            member: member.clone(),
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+
+    assert!(client.has_access(&owner, &merchant));
+    assert!(client.has_access(&member, &merchant));
+    assert!(!client.has_access(&outsider, &merchant));
+    assert_eq!(
+        client.get_team_owner(&member, &merchant),
+        Some(owner.clone())
+    );
+    assert_eq!(
+        client.get_seat_plan(&owner, &merchant).unwrap().members,
+        vec![&env, member.clone()]
+    );
+
+    // Taking the seat back revokes access
+    client.remove_member(&owner, &merchant, &member);
+    assert!(!client.has_access(&member, &merchant));
+    assert_eq!(client.get_team_owner(&member, &merchant), None);
+
+    // Members lose access along with the owner, once the paid period ends
+    client.add_member(&owner, &merchant, &member);
+    client.cancel_subscription(&owner, &merchant);
+    assert!(client.has_access(&member, &merchant));
+    advance_time(&env, 3600);
+    assert!(!client.has_access(&owner, &merchant));
+    assert!(!client.has_access(&member, &merchant));
+}
+
+#[test]
+#[should_panic(expected = "No free seats")]
+fn test_team_member_limited_to_seats() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let owner = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.create_team_subscription(&owner, &merchant, &token_client.address, &100, &1, &3600);
+    client.add_member(&owner, &merchant, &Address::generate(&env));
+    client.add_member(&owner, &merchant, &Address::generate(&env));
+}
+
+#[test]
+fn test_team_seat_change_applies_next_cycle() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let owner = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&owner, &1000);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&owner, &contract_id, &1000, &expiration_ledger);
+
+    client.create_team_subscription(&owner, &merchant, &token, &100, &3, &3600);
+    client.collect_payment(&owner, &merchant);
+    assert_eq!(token_client.balance(&merchant), 300);
+
+    let plan = client.set_seats(&owner, &merchant, &5);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [SeatsScheduled {
+            owner: owner.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            seats: 5,
+            next_amount: 500,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!((plan.seats, plan.next_seats), (3, 5));
+
+    // The extra seats can't be handed out before they are paid for
+    for _ in 0..3 {
+        client.add_member(&owner, &merchant, &Address::generate(&env));
+    }
+    assert!(client
+        .try_add_member(&owner, &merchant, &Address::generate(&env))
+        .is_err());
+
+    // The next collection bills the new seat count
+    advance_time(&env, 3600);
+    client.collect_payment(&owner, &merchant);
+    assert_eq!(token_client.balance(&merchant), 800);
+    assert_eq!(client.get_subscription(&owner, &merchant).amount, 500);
+    assert_eq!(client.get_seat_plan(&owner, &merchant).unwrap().seats, 5);
+    client.add_member(&owner, &merchant, &Address::generate(&env));
+}
+
+#[test]
+fn test_team_seat_price_change_applies_next_cycle() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let owner = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&owner, &1000);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&owner, &contract_id, &1000, &expiration_ledger);
+
+    client.create_team_subscription(&owner, &merchant, &token, &100, &3, &3600);
+    client.collect_payment(&owner, &merchant);
+
+    // The merchant schedules a new price, which doesn't touch the paid period
+    let plan = client.set_seat_price(&owner, &merchant, &120);
+    assert_eq!(env.auths()[0].0, merchant);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [SeatPriceScheduled {
+            owner: owner.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            price_per_seat: 120,
+            next_amount: 360,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert_eq!((plan.price_per_seat, plan.next_price_per_seat), (100, 120));
+    assert_eq!(client.get_subscription(&owner, &merchant).amount, 300);
+
+    // A seat change in the same cycle is billed at the new price
+    let plan = client.set_seats(&owner, &merchant, &4);
+    assert_eq!(plan.next_price_per_seat, 120);
+
+    // The next collection bills both changes
+    advance_time(&env, 3600);
+    assert_eq!(client.collect_payment(&owner, &merchant), 480);
+    assert_eq!(client.get_subscription(&owner, &merchant).amount, 480);
+    let plan = client.get_seat_plan(&owner, &merchant).unwrap();
+    assert_eq!((plan.price_per_seat, plan.seats), (120, 4));
+    assert_eq!(token_client.balance(&merchant), 780);
+}
+
+#[test]
+fn test_team_seat_change_waits_for_payment() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let owner = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&owner, &600);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&owner, &contract_id, &1000, &expiration_ledger);
+
+    client.create_team_subscription(&owner, &merchant, &token, &100, &3, &3600);
+    client.collect_payment(&owner, &merchant);
+    client.set_seats(&owner, &merchant, &5);
+    for _ in 0..3 {
+        client.add_member(&owner, &merchant, &Address::generate(&env));
+    }
+
+    // The owner can't cover the bigger charge, so the change stays pending
+    advance_time(&env, 3600);
+    assert_eq!(client.collect_payment(&owner, &merchant), 0);
+    assert_eq!(token_client.balance(&owner), 300);
+    let subscription = client.get_subscription(&owner, &merchant);
+    assert_eq!(subscription.status, SubscriptionStatus::PastDue);
+    assert_eq!(subscription.amount, 300);
+    let plan = client.get_seat_plan(&owner, &merchant).unwrap();
+    assert_eq!((plan.seats, plan.next_seats), (3, 5));
+    assert!(client
+        .try_add_member(&owner, &merchant, &Address::generate(&env))
+        .is_err());
+
+    // Once it is paid for, the new seats can be handed out
+    stellar_client.mint(&owner, &200);
+    assert_eq!(client.collect_payment(&owner, &merchant), 500);
+    assert_eq!(client.get_seat_plan(&owner, &merchant).unwrap().seats, 5);
+    client.add_member(&owner, &merchant, &Address::generate(&env));
+}
+
+#[test]
+#[should_panic(expected = "Remove members before reducing seats")]
+fn test_team_seats_not_below_members() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let owner = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    client.create_team_subscription(&owner, &merchant, &token_client.address, &100, &2, &3600);
+    client.add_member(&owner, &merchant, &Address::generate(&env));
+    client.add_member(&owner, &merchant, &Address::generate(&env));
+    client.set_seats(&owner, &merchant, &1);
+}
+
+// ============================================================================
+// ACCESS TESTS
+// ============================================================================
+
+#[test]
+fn test_has_access_covers_grace_period() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    assert!(!client.has_access(&subscriber, &merchant));
+
+    stellar_client.mint(&subscriber, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+    client.create_subscription(&subscriber, &merchant, &token, &100, &3600);
+    client.collect_payment(&subscriber, &merchant);
+    assert!(client.has_access(&subscriber, &merchant));
+
+    // The next payment fails, but the grace period keeps access
+    advance_time(&env, 3600);
+    assert_eq!(client.collect_payment(&subscriber, &merchant), 0);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::PastDue
+    );
+    assert!(client.has_access(&subscriber, &merchant));
+
+    // Until the grace period runs out, even before it is suspended
+    advance_time(&env, PAST_DUE_GRACE_PERIOD);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::PastDue
+    );
+    assert!(!client.has_access(&subscriber, &merchant));
+}
+
+#[test]
+fn test_has_access_by_status() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    stellar_client.mint(&subscriber, &100);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(&subscriber, &contract_id, &100, &expiration_ledger);
+
+    // Trials give access
+    client.create_trial_subscription(&subscriber, &merchant, &token, &100, &3600, &600);
+    assert!(client.has_access(&subscriber, &merchant));
+
+    // Paused subscriptions don't
+    client.pause_subscription(&subscriber, &merchant);
+    assert!(!client.has_access(&subscriber, &merchant));
+    client.resume_subscription(&subscriber, &merchant);
+
+    // Cancelled subscriptions keep access for the period already paid for
+    advance_time(&env, 600);
+    client.collect_payment(&subscriber, &merchant);
+    client.cancel_subscription(&subscriber, &merchant);
+    assert!(client.has_access(&subscriber, &merchant));
+    advance_time(&env, 3600);
+    assert!(!client.has_access(&subscriber, &merchant));
+}
+
+#[test]
+fn test_has_access_requires_payment() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (_, client) = setup_contract(&env, &liquidity_pool);
+
+    // A subscription that was never funded gives no access
+    client.create_subscription(&subscriber, &merchant, &token, &100, &3600);
+    assert!(!client.has_access(&subscriber, &merchant));
+    assert_eq!(client.collect_payment(&subscriber, &merchant), 0);
+    assert!(!client.has_access(&subscriber, &merchant));
+
+    // Nor does re-creating it
+    client.cancel_subscription(&subscriber, &merchant);
+    client.create_subscription(&subscriber, &merchant, &token, &100, &3600);
+    assert!(!client.has_access(&subscriber, &merchant));
+}
+
+// ============================================================================
+// STATUS TESTS
+// ============================================================================
+
+const ALL_STATUSES: [SubscriptionStatus; 7] = [
+    SubscriptionStatus::Trialing,
+    SubscriptionStatus::Active,
+    SubscriptionStatus::Paused,
+    SubscriptionStatus::PastDue,
+    SubscriptionStatus::Suspended,
+    SubscriptionStatus::Completed,
+    SubscriptionStatus::Cancelled,
+];
+
+/// The lifecycle state machine, spelled out independently of the contract
+fn transition_allowed(from: SubscriptionStatus, to: SubscriptionStatus) -> bool {
+    use SubscriptionStatus::*;
+    let allowed: &[SubscriptionStatus] = match from {
+        Trialing => &[Active, PastDue, Paused, Completed, Cancelled],
+        Active => &[PastDue, Paused, Completed, Cancelled],
+        PastDue => &[Active, Suspended, Completed, Cancelled],
+        Paused => &[Active, Completed, Cancelled],
+        Suspended => &[Active, Completed, Cancelled],
+        Completed | Cancelled => &[],
+    };
+    allowed.contains(&to)
+}
+
+/// Lifecycle entrypoints exercised against every status
+#[derive(Clone, Copy, Debug)]
+enum Action {
+    Collect,
+    Pause,
+    Resume,
+    Cancel,
+    Complete,
+}
+
+/// Helper to drive a fresh subscription into `status` (with its next payment
+/// due and enough funds to pay it), then run `action` against it
+fn run_action_in_status(
+    status: SubscriptionStatus,
+    action: Action,
+) -> (
+    Result<SubscriptionStatus, soroban_sdk::Error>,
+    SubscriptionStatus,
+) {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    let amount: i128 = 100;
+    let interval: u64 = 30 * 24 * 60 * 60;
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
+
+    match status {
+        SubscriptionStatus::Trialing => {
+            client.create_trial_subscription(
+                &subscriber,
+                &merchant,
+                &token,
+                &amount,
+                &interval,
+                &interval,
+            );
+            advance_time(&env, interval);
+        }
+        SubscriptionStatus::Active | SubscriptionStatus::Paused => {
+            client.create_subscription(&subscriber, &merchant, &token, &amount, &interval);
+            stellar_client.mint(&subscriber, &amount);
+            client.collect_payment(&subscriber, &merchant);
+            advance_time(&env, interval);
+            if status == SubscriptionStatus::Paused {
+                client.pause_subscription(&subscriber, &merchant);
+            }
+        }
+        SubscriptionStatus::PastDue | SubscriptionStatus::Suspended => {
+            // No balance and no credit for BNPL
+            client.create_subscription(&subscriber, &merchant, &token, &amount, &interval);
+            client.collect_payment(&subscriber, &merchant);
+            if status == SubscriptionStatus::Suspended {
+                advance_time(&env, 7 * 24 * 60 * 60);
+                client.collect_payment(&subscriber, &merchant);
+            }
+        }
+        SubscriptionStatus::Completed => {
+            client.create_subscription(&subscriber, &merchant, &token, &amount, &interval);
+            client.complete_subscription(&subscriber, &merchant);
+        }
+        SubscriptionStatus::Cancelled => {
+            client.create_subscription(&subscriber, &merchant, &token, &amount, &interval);
+            client.cancel_subscription(&subscriber, &merchant);
+        }
+    }
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        status
+    );
+    stellar_client.mint(&subscriber, &(amount * 5));
+
+    let result = match action {
+        Action::Collect => client
+            .try_collect_payment(&subscriber, &merchant)
+            .map(|_| SubscriptionStatus::Active),
+        Action::Pause => client
+            .try_pause_subscription(&subscriber, &merchant)
+            .map(|_| SubscriptionStatus::Paused),
+        Action::Resume => client
+            .try_resume_subscription(&subscriber, &merchant)
+            .map(|_| SubscriptionStatus::Active),
+        Action::Cancel => client
+            .try_cancel_subscription(&subscriber, &merchant)
+            .map(|_| SubscriptionStatus::Cancelled),
+        Action::Complete => client
+            .try_complete_subscription(&subscriber, &merchant)
+            .map(|_| SubscriptionStatus::Completed),
+    };
+    (
+        result.map_err(|err| err.unwrap()),
+        client.get_subscription(&subscriber, &merchant).status,
+    )
+}
+
+#[test]
+fn test_status_transition_table() {
+    for from in ALL_STATUSES {
+        for to in ALL_STATUSES {
+            assert_eq!(
+                from.can_transition_to(to),
+                transition_allowed(from, to),
+                "{:?} -> {:?}",
+                from,
+                to
+            );
+        }
+        assert_eq!(
+            from.is_live(),
+            !matches!(
+                from,
+                SubscriptionStatus::Completed | SubscriptionStatus::Cancelled
+            )
+        );
+    }
+}
+
+#[test]
+fn test_entrypoints_enforce_status_machine() {
+    let actions = [
+        Action::Collect,
+        Action::Pause,
+        Action::Resume,
+        Action::Cancel,
+        Action::Complete,
+    ];
+    for status in ALL_STATUSES {
+        for action in actions {
+            let (expected, error) = match action {
+                // Only billable states collect; every successful payment
+                // leaves the subscription active
+                Action::Collect => (
+                    matches!(
+                        status,
+                        SubscriptionStatus::Trialing
+                            | SubscriptionStatus::Active
+                            | SubscriptionStatus::PastDue
+                    )
+                    .then_some(SubscriptionStatus::Active),
+                    SubscriptionError::NotCollectible,
+                ),
+                Action::Pause => (
+                    transition_allowed(status, SubscriptionStatus::Paused)
+                        .then_some(SubscriptionStatus::Paused),
+                    SubscriptionError::InvalidTransition,
+                ),
+                // Resuming only lifts a pause or a suspension
+                Action::Resume => (
+                    matches!(
+                        status,
+                        SubscriptionStatus::Paused | SubscriptionStatus::Suspended
+                    )
+                    .then_some(SubscriptionStatus::Active),
+                    SubscriptionError::InvalidTransition,
+                ),
+                Action::Cancel => (
+                    transition_allowed(status, SubscriptionStatus::Cancelled)
+                        .then_some(SubscriptionStatus::Cancelled),
+                    SubscriptionError::InvalidTransition,
+                ),
+                Action::Complete => (
+                    transition_allowed(status, SubscriptionStatus::Completed)
+                        .then_some(SubscriptionStatus::Completed),
+                    SubscriptionError::InvalidTransition,
+                ),
+            };
+
+            let (result, after) = run_action_in_status(status, action);
+            match expected {
+                Some(target) => {
+                    assert_eq!(result, Ok(target), "{:?} in {:?}", action, status);
+                    assert_eq!(after, target, "{:?} in {:?}", action, status);
+                }
+                None => {
+                    assert_eq!(result, Err(error.into()), "{:?} in {:?}", action, status);
+                    assert_eq!(after, status, "{:?} in {:?}", action, status);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_failed_collections_suspend_after_grace_period() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    let amount: i128 = 100;
+    let interval: u64 = 30 * 24 * 60 * 60;
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &amount,
+        &interval,
+    );
+
+    // A failed collection is recorded rather than reverted
+    assert_eq!(client.collect_payment(&subscriber, &merchant), 0);
+    assert_eq!(
+        env.events().all().filter_by_contract(&contract_id),
+        [StatusChanged {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            version: EVENT_VERSION,
+            from: SubscriptionStatus::Active,
+            to: SubscriptionStatus::PastDue,
+        }
+        .to_xdr(&env, &contract_id)]
+    );
+    assert!(client.can_process_payment(&subscriber, &merchant));
+
+    // Retries within the grace period keep it past due
+    advance_time(&env, 6 * 24 * 60 * 60);
+    assert_eq!(client.collect_payment(&subscriber, &merchant), 0);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::PastDue
+    );
+
+    // After that it is suspended until the subscriber resumes it
+    advance_time(&env, 24 * 60 * 60);
+    assert_eq!(client.collect_payment(&subscriber, &merchant), 0);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::Suspended
+    );
+    assert!(!client.can_process_payment(&subscriber, &merchant));
+
+    stellar_client.mint(&subscriber, &amount);
+    client.resume_subscription(&subscriber, &merchant);
+    assert_eq!(client.collect_payment(&subscriber, &merchant), amount);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn test_trial_delays_first_payment() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, stellar_client) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    let amount: i128 = 100;
+    let interval: u64 = 30 * 24 * 60 * 60;
+    let trial_period: u64 = 14 * 24 * 60 * 60;
+    stellar_client.mint(&subscriber, &amount);
+    let expiration_ledger = env.ledger().sequence() + 1000;
+    token_client.approve(
+        &subscriber,
+        &contract_id,
+        &(amount * 10),
+        &expiration_ledger,
+    );
+
+    let subscription = client.create_trial_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &amount,
+        &interval,
+        &trial_period,
+    );
+    assert_eq!(subscription.status, SubscriptionStatus::Trialing);
+    assert_eq!(
+        subscription.starts_at,
+        env.ledger().timestamp() + trial_period
+    );
+    assert!(!client.can_process_payment(&subscriber, &merchant));
+    assert!(client.try_collect_payment(&subscriber, &merchant).is_err());
+
+    // The first payment ends the trial
+    advance_time(&env, trial_period);
+    assert_eq!(client.collect_payment(&subscriber, &merchant), amount);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn test_upgrade_converts_legacy_subscription_status() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant_a = Address::generate(&env);
+    let merchant_b = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+
+    let contract_id = env.register(SoroSubV1, ());
+    let v1 = SoroSubV1Client::new(&env, &contract_id);
+    v1.initialize(&admin, &liquidity_pool);
+    for (merchant, is_active) in [(&merchant_a, true), (&merchant_b, false)] {
+        v1.record_subscription(&SubscriptionV1 {
+            subscriber: subscriber.clone(),
+            merchant: merchant.clone(),
+            token: token_client.address.clone(),
+            amount: 100,
+            interval: 30 * 24 * 60 * 60,
+            last_payment_time: 500,
+            is_active,
+            credit_score: 30,
+        });
+    }
+
+    let client = upgrade_to_current(&env, &contract_id);
+    let active = client.get_subscription(&subscriber, &merchant_a);
+    assert_eq!(active.status, SubscriptionStatus::Active);
+    assert_eq!(active.last_payment_time, 500);
+    assert_eq!(active.credit_score, 30);
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant_b).status,
+        SubscriptionStatus::Cancelled
+    );
+
+    // Migration rewrites both in the current layout
+    let keys = vec![
+        &env,
+        SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant_a.clone(),
+        },
+        SubscriptionKey {
+            subscriber: subscriber.clone(),
+            merchant: merchant_b.clone(),
+        },
+    ];
+    assert_eq!(client.migrate(&vec![&env], &keys), 2);
+    assert_eq!(client.migrate(&vec![&env], &keys), 0);
+    assert_eq!(
+        client
+            .list_subscriptions_by_subscriber(&subscriber, &0, &10)
+            .len(),
+        1
+    );
+}
+
+// ============================================================================
+// TTL TESTS
+// ============================================================================
+
+// The test host restores archived entries on access, so these tests read
+// TTLs directly: an entry is live while its TTL is above zero.
+
+/// Helper to read a persistent entry's remaining TTL without touching it
+fn persistent_ttl(env: &Env, contract_id: &Address, key: &DataKey) -> u32 {
+    env.as_contract(contract_id, || env.storage().persistent().get_ttl(key))
+}
+
+/// Helper to read the contract instance's remaining TTL
+fn instance_ttl(env: &Env, contract_id: &Address) -> u32 {
+    env.as_contract(contract_id, || env.storage().instance().get_ttl())
+}
+
+#[test]
+fn test_bump_subscription_keeps_it_live() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &100,
+        &(365 * 24 * 60 * 60),
+    );
+    let key = SubscriptionKey {
+        subscriber: subscriber.clone(),
+        merchant: merchant.clone(),
+    };
+    let entries = [
+        DataKey::Subscription(key.clone()),
+        DataKey::IndexPosition(key),
+        DataKey::SubscriberIndex(subscriber.clone(), 0),
+        DataKey::SubscriberIndexLen(subscriber.clone()),
+        DataKey::MerchantIndex(merchant.clone(), 0),
+        DataKey::MerchantIndexLen(merchant.clone()),
+    ];
+
+    // Three times the maximum entry TTL passes; a permissionless bump in
+    // between keeps everything live
+    for _ in 0..5 {
+        advance_ledgers(&env, 6_000);
+        assert!(instance_ttl(&env, &contract_id) > 0);
+        for entry in entries.iter() {
+            assert!(persistent_ttl(&env, &contract_id, entry) > 0);
+        }
+        client.bump_subscription(&subscriber, &merchant);
+        assert_eq!(env.auths().len(), 0);
+    }
+    assert_eq!(
+        client.get_subscription(&subscriber, &merchant).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn test_bump_debt_keeps_it_live() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let token = token_client.address.clone();
+
+    // Outstanding debt with one unpaid advance, owed to the v1 pool
+    let contract_id = env.register(SoroSubV1, ());
+    let v1 = SoroSubV1Client::new(&env, &contract_id);
+    v1.initialize(&admin, &liquidity_pool);
+    v1.record_debt(&user, &token, &150);
+    let client = upgrade_to_current(&env, &contract_id);
+    client.get_user_debt(&user);
+    client.set_auto_repay(&user, &5_000);
+
+    let entries = [
+        DataKey::UserDebt(user.clone()),
+        DataKey::Advance(user.clone(), 0),
+        DataKey::AutoRepay(user.clone()),
+    ];
+
+    for _ in 0..5 {
+        advance_ledgers(&env, 6_000);
+        assert!(instance_ttl(&env, &contract_id) > 0);
+        for entry in entries.iter() {
+            assert!(persistent_ttl(&env, &contract_id, entry) > 0);
+        }
+        client.bump_debt(&user);
+    }
+    assert_eq!(client.get_user_debt(&user).unwrap().amount, 150);
+}
+
+#[test]
+fn test_touching_a_subscription_extends_its_ttl() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let admin = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (token_client, _) = create_token(&env, &admin);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &token_client.address,
+        &100,
+        &(30 * 24 * 60 * 60),
+    );
+    let storage_key = DataKey::Subscription(SubscriptionKey {
+        subscriber: subscriber.clone(),
+        merchant: merchant.clone(),
+    });
+    let full_ttl = persistent_ttl(&env, &contract_id, &storage_key);
+
+    // Reads top the entry back up...
+    advance_ledgers(&env, 6_000);
+    assert_eq!(
+        persistent_ttl(&env, &contract_id, &storage_key),
+        full_ttl - 6_000
+    );
+    client.get_subscription(&subscriber, &merchant);
+    assert_eq!(persistent_ttl(&env, &contract_id, &storage_key), full_ttl);
+
+    // ...and so does cancelling
+    advance_ledgers(&env, 6_000);
+    client.cancel_subscription(&subscriber, &merchant);
+    assert_eq!(persistent_ttl(&env, &contract_id, &storage_key), full_ttl);
+    assert_eq!(instance_ttl(&env, &contract_id), full_ttl);
+}
+
+#[test]
+fn test_checking_a_role_keeps_it_live() {
+    let env = Env::default();
+    env.mock_all_auths();
+    setup_ledger(&env);
+
+    let arbiter = Address::generate(&env);
+    let liquidity_pool = Address::generate(&env);
+    let (contract_id, client) = setup_contract(&env, &liquidity_pool);
+    client.grant_role(&Role::Arbiter, &arbiter);
+
+    // Roles are per-account entries, not part of the instance
+    let key = DataKey::Role(Role::Arbiter, arbiter.clone());
+    assert!(!env.as_contract(&contract_id, || env.storage().instance().has(&key)));
+
+    for _ in 0..5 {
+        advance_ledgers(&env, 6_000);
+        assert!(persistent_ttl(&env, &contract_id, &key) > 0);
+        assert!(client.has_role(&Role::Arbiter, &arbiter));
+    }
+    client.revoke_role(&Role::Arbiter, &arbiter);
+    assert!(!client.has_role(&Role::Arbiter, &arbiter));
 }
@@ -50,7 +50,9 @@ export function useWalletData() {
     try {
       // Fetch all data in parallel
       const [subscriptions, balance, debt, creditScore] = await Promise.all([
-        getUserSubscriptions(publicKey).catch(() => []),
+        getUserSubscriptions(publicKey)
+          .then(subs => subs.map(sub => ({ ...sub, providerAddress: sub.merchant })))
+          .catch(() => []),
         getTokenBalance(publicKey).catch(() => 0),
         getUserDebt(publicKey).catch(() => BigInt(0)),
         getCreditScore(publicKey).catch(() => 750),
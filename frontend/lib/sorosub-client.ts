@@ -6,13 +6,26 @@ import { CONTRACTS, NETWORK, getSorobanServer, toStroops, fromStroops } from './
 
 const { Contract, TransactionBuilder, Address, nativeToScVal } = StellarSdk;
 
+// Lifecycle state of a subscription (mirrors the contract's SubscriptionStatus)
+export type SubscriptionStatus =
+    | 'Trialing'
+    | 'Active'
+    | 'Paused'
+    | 'PastDue'
+    | 'Suspended'
+    | 'Completed'
+    | 'Cancelled';
+
 // Subscription data returned from contract
 export interface Subscription {
+    merchant: string;
+    payer: string; // Address paying for it (the subscriber unless gifted)
     token: string;
     amount: bigint;
     interval: bigint;
     lastPayment: bigint;
-    isActive: boolean;
+    status: SubscriptionStatus;
+    isActive: boolean; // Still running (not completed or cancelled)
 }
 
 // Get the current ledger sequence from the network
@@ -226,6 +239,12 @@ function parseSubscription(scVal: StellarSdk.xdr.ScVal): Subscription {
         const val = entry.val();
 
         switch (key) {
+            case 'merchant':
+                result.merchant = Address.fromScVal(val).toString();
+                break;
+            case 'payer':
+                result.payer = Address.fromScVal(val).toString();
+                break;
             case 'token':
                 result.token = Address.fromScVal(val).toString();
                 break;
@@ -238,8 +257,10 @@ function parseSubscription(scVal: StellarSdk.xdr.ScVal): Subscription {
             case 'last_payment':
                 result.lastPayment = val.u64().toBigInt();
                 break;
-            case 'is_active':
-                result.isActive = val.b();
+            case 'status':
+                // Unit enum variants are encoded as a single-symbol vec
+                result.status = val.vec()![0].sym().toString() as SubscriptionStatus;
+                result.isActive = result.status !== 'Completed' && result.status !== 'Cancelled';
                 break;
         }
     }
@@ -479,11 +500,41 @@ export async function canProcessPayment(
     }
 }
 
-// Get all active subscriptions for a user
-export async function getUserSubscriptions(subscriberAddress: string): Promise<any[]> {
+// Get a page of a user's active subscriptions from the on-chain index
+export async function getUserSubscriptions(
+    subscriberAddress: string,
+    offset: number = 0,
+    limit: number = 50
+): Promise<Subscription[]> {
     try {
-        // For now, return empty array since we need to query individual subscriptions
-        // In a real implementation, you'd query all providers or maintain an index
+        const server = getSorobanServer();
+        const contract = new Contract(CONTRACTS.SOROSUB);
+
+        const subscriber = new Address(subscriberAddress);
+        const account = await server.getAccount(subscriberAddress);
+
+        const transaction = new TransactionBuilder(account, {
+            fee: '100',
+            networkPassphrase: NETWORK.networkPassphrase,
+        })
+            .addOperation(
+                contract.call(
+                    'list_subscriptions_by_subscriber',
+                    subscriber.toScVal(),
+                    nativeToScVal(offset, { type: 'u32' }),
+                    nativeToScVal(limit, { type: 'u32' })
+                )
+            )
+            .setTimeout(30)
+            .build();
+
+        const response = await server.simulateTransaction(transaction);
+
+        if ('result' in response && response.result) {
+            const entries = response.result.retval.vec() ?? [];
+            return entries.map(parseSubscription);
+        }
+
         return [];
     } catch (error) {
         console.error('Error fetching user subscriptions:', error);